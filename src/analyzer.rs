@@ -0,0 +1,206 @@
+/// Looks out for things programs do that usually mean a bug, or code that depends on how
+/// a particular interpreter behaves: self-modifying code, jumps into data, deep or
+/// unbalanced calls, and memory accesses past the end of RAM
+use log::warn;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::emulator::{Emulator, MemoryAccess, RAM_SIZE};
+use crate::symbols::Symbols;
+
+const MAX_CALL_DEPTH: usize = 16; // the stack size of the original interpreter
+const BACKTRACE_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Suspicion {
+    WriteToCode,
+    JumpIntoData,
+    DeepCall,
+    ReturnWithEmptyStack,
+    SpritePastRam,
+    IndexPastRam,
+}
+
+impl Suspicion {
+    fn name(&self) -> &'static str {
+        match self {
+            Suspicion::WriteToCode => "WRITE TO CODE",
+            Suspicion::JumpIntoData => "JUMP INTO DATA",
+            Suspicion::DeepCall => "DEEP CALL",
+            Suspicion::ReturnWithEmptyStack => "RETURN WITH EMPTY STACK",
+            Suspicion::SpritePastRam => "SPRITE PAST RAM",
+            Suspicion::IndexPastRam => "INDEX PAST RAM",
+        }
+    }
+}
+
+/// The first time something suspicious happened at an address, and how often it did
+struct Finding {
+    detail: String,
+    cycle: u64,
+    backtrace: String,
+    count: u64,
+}
+
+pub struct Analyzer {
+    executed: Vec<bool>,
+    read: Vec<bool>,
+    stack: Vec<u16>, // the stack before the instruction being checked, for backtraces
+    findings: BTreeMap<(u16, Suspicion), Finding>,
+    symbols: Symbols,
+}
+
+impl Analyzer {
+    /// An analyzer labelling addresses in backtraces with `symbols`
+    pub fn new(symbols: Symbols) -> Self {
+        Self {
+            executed: vec![false; RAM_SIZE],
+            read: vec![false; RAM_SIZE],
+            stack: Vec::new(),
+            findings: BTreeMap::new(),
+            symbols,
+        }
+    }
+
+    /// Check the next instruction for problems that are only visible before it runs
+    pub fn before(&mut self, emu: &Emulator) {
+        self.stack.clear();
+        self.stack.extend_from_slice(emu.stack());
+        let pc = emu.pc();
+        let opcode = emu.instruction_at(pc);
+        let x = ((opcode >> 8) & 0xF) as usize;
+        match opcode & 0xF000 {
+            0x0000 if opcode == 0x00EE && emu.stack().is_empty() => self.report(
+                Suspicion::ReturnWithEmptyStack,
+                pc,
+                emu,
+                "returned with nothing on the stack".to_string(),
+            ),
+            0xD000 if emu.i() as usize + (opcode & 0xF) as usize > RAM_SIZE => self.report(
+                Suspicion::SpritePastRam,
+                pc,
+                emu,
+                format!(
+                    "drew {} rows from I={:03X}, past the end of memory",
+                    opcode & 0xF,
+                    emu.i()
+                ),
+            ),
+            0xF000 if opcode & 0xFF == 0x1E && emu.i() + emu.registers()[x] as u16 > 0xFFF => self
+                .report(
+                    Suspicion::IndexPastRam,
+                    pc,
+                    emu,
+                    format!(
+                        "added V{:X}={:02X} to I={:03X}, past 0xFFF",
+                        x,
+                        emu.registers()[x],
+                        emu.i()
+                    ),
+                ),
+            _ => {}
+        }
+    }
+
+    /// Check what the instruction `opcode` at `pc` just did
+    pub fn after(&mut self, pc: u16, opcode: u16, emu: &Emulator) {
+        let mut wrote_to_code = None;
+        for access in emu.last_accesses() {
+            match *access {
+                MemoryAccess::Read(address) => self.read[address as usize % RAM_SIZE] = true,
+                MemoryAccess::Write(address) if self.executed[address as usize % RAM_SIZE] => {
+                    wrote_to_code.get_or_insert(address);
+                }
+                MemoryAccess::Write(_) => {}
+            }
+        }
+        if let Some(address) = wrote_to_code {
+            self.report(
+                Suspicion::WriteToCode,
+                pc,
+                emu,
+                format!("wrote to {:03X}, which was executed before", address),
+            );
+        }
+
+        let jumped = matches!(opcode & 0xF000, 0x1000 | 0x2000 | 0xB000) || opcode == 0x00EE;
+        let target = emu.pc() as usize % RAM_SIZE;
+        if jumped && (self.read[target] || self.read[(target + 1) % RAM_SIZE]) {
+            self.report(
+                Suspicion::JumpIntoData,
+                pc,
+                emu,
+                format!("jumped to {:03X}, which was read as data before", target),
+            );
+        }
+        if opcode & 0xF000 == 0x2000 && emu.stack().len() > MAX_CALL_DEPTH {
+            self.report(
+                Suspicion::DeepCall,
+                pc,
+                emu,
+                format!(
+                    "called {} levels deep, more than {}",
+                    emu.stack().len(),
+                    MAX_CALL_DEPTH
+                ),
+            );
+        }
+
+        self.executed[pc as usize % RAM_SIZE] = true;
+        self.executed[(pc as usize + 1) % RAM_SIZE] = true;
+    }
+
+    /// Write every kind of suspicious behaviour found to `file`, by address
+    pub fn save(&self, file: &str) -> Result<(), String> {
+        let mut report = format!("{} suspicious behaviours found\n", self.findings.len());
+        for ((pc, suspicion), finding) in &self.findings {
+            report += &format!(
+                "\n{} at {:03X}, first at cycle {}, {}: {}\n  backtrace: {}\n",
+                suspicion.name(),
+                pc,
+                finding.cycle,
+                match finding.count {
+                    1 => "once".to_string(),
+                    count => format!("{} times", count),
+                },
+                finding.detail,
+                finding.backtrace
+            );
+        }
+        fs::write(file, report).map_err(|e| format!("Could not write {}: {}", file, e))
+    }
+
+    fn report(&mut self, suspicion: Suspicion, pc: u16, emu: &Emulator, detail: String) {
+        if let Some(finding) = self.findings.get_mut(&(pc, suspicion)) {
+            finding.count += 1;
+            return;
+        }
+        let backtrace = self.backtrace(pc);
+        warn!(
+            "{} at {:03X}: {}, backtrace: {}",
+            suspicion.name(),
+            pc,
+            detail,
+            backtrace
+        );
+        self.findings.insert(
+            (pc, suspicion),
+            Finding {
+                detail,
+                cycle: emu.cycles(),
+                backtrace,
+                count: 1,
+            },
+        );
+    }
+
+    /// `pc` followed by the calls that led to it, the most recent first
+    fn backtrace(&self, pc: u16) -> String {
+        let mut backtrace = self.symbols.backtrace(pc, &self.stack);
+        if backtrace.len() > BACKTRACE_LENGTH {
+            backtrace.truncate(BACKTRACE_LENGTH);
+            backtrace.push("...".to_string());
+        }
+        backtrace.join(" <- ")
+    }
+}
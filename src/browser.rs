@@ -0,0 +1,147 @@
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::text::{draw_text, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+const TEXT_SCALE: u32 = 3;
+const MARGIN: i32 = 12;
+const LINE_HEIGHT: i32 = ((GLYPH_HEIGHT + 2) * TEXT_SCALE) as i32;
+const PAGE_SIZE: usize = 10; // how far Page Up and Page Down move the selection
+const BACKGROUND_COLOR: Color = Color::RGB(0x10, 0x10, 0x18);
+const TEXT_COLOR: Color = Color::RGB(0xC0, 0xC0, 0xC0);
+const SELECTED_COLOR: Color = Color::RGB(0xFF, 0xD7, 0x00);
+
+/// List of the ROMs in a directory, drawn over the whole window, to pick a game to load
+pub struct RomBrowser {
+    dir: PathBuf,
+    entries: Vec<PathBuf>,
+    selected: usize,
+    open: bool,
+}
+
+impl RomBrowser {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            entries: Vec::new(),
+            selected: 0,
+            open: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Show the browser, listing the ROMs currently in the directory
+    pub fn open(&mut self) {
+        self.entries = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| is_rom(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.entries.sort();
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        self.open = true;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Move the selection with the arrow, page and home/end keys.
+    /// Returns the selected ROM when enter is pressed, the browser stays open until it's closed,
+    /// so it can still be used if the ROM fails to load
+    pub fn handle_key(&mut self, key: Keycode) -> Option<PathBuf> {
+        let last = self.entries.len().saturating_sub(1);
+        match key {
+            Keycode::UP => self.selected = self.selected.saturating_sub(1),
+            Keycode::DOWN => self.selected = (self.selected + 1).min(last),
+            Keycode::PAGEUP => self.selected = self.selected.saturating_sub(PAGE_SIZE),
+            Keycode::PAGEDOWN => self.selected = (self.selected + PAGE_SIZE).min(last),
+            Keycode::HOME => self.selected = 0,
+            Keycode::END => self.selected = last,
+            Keycode::RETURN | Keycode::KP_ENTER => {
+                return self.entries.get(self.selected).cloned();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Draw the list of ROMs over the whole canvas, scrolled to keep the selection in view
+    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let (width, height) = canvas.output_size()?;
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.fill_rect(Rect::new(0, 0, width, height))?;
+
+        let max_chars =
+            (width as i32 - MARGIN * 2) as usize / ((GLYPH_WIDTH + 1) * TEXT_SCALE) as usize;
+        canvas.set_draw_color(SELECTED_COLOR);
+        let title = format!("ROMS IN {}", self.dir.display());
+        draw_text(
+            canvas,
+            &truncate(&title, max_chars),
+            MARGIN,
+            MARGIN,
+            TEXT_SCALE,
+        )?;
+
+        let list_top = MARGIN + LINE_HEIGHT * 2;
+        if self.entries.is_empty() {
+            canvas.set_draw_color(TEXT_COLOR);
+            return draw_text(canvas, "NO ROMS FOUND", MARGIN, list_top, TEXT_SCALE);
+        }
+
+        let visible_lines = ((height as i32 - list_top - MARGIN) / LINE_HEIGHT).max(1) as usize;
+        let first = (self.selected + 1).saturating_sub(visible_lines);
+        for (line, (index, rom)) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(visible_lines)
+            .enumerate()
+        {
+            let name = rom.file_name().unwrap_or_default().to_string_lossy();
+            let (color, marker) = if index == self.selected {
+                (SELECTED_COLOR, "> ")
+            } else {
+                (TEXT_COLOR, "  ")
+            };
+            canvas.set_draw_color(color);
+            let text = truncate(&format!("{}{}", marker, name), max_chars);
+            draw_text(
+                canvas,
+                &text,
+                MARGIN,
+                list_top + line as i32 * LINE_HEIGHT,
+                TEXT_SCALE,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Tells if the file has one of the extensions used for CHIP8 ROMs
+fn is_rom(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| ROM_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Cut `text` down to at most `max_chars` characters
+fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
@@ -0,0 +1,89 @@
+/// Layers the command line options on top of environment variables and a config file:
+/// built-in defaults < config file < environment variables < command line flags
+use clap::Command;
+use log::{info, warn};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use toml::Value;
+
+/// Prefix of the environment variables for each option, e.g. `CHIP8_FOREGROUND`
+const ENV_PREFIX: &str = "CHIP8_";
+
+/// Environment variable to use a config file other than the one in the config directory
+const CONFIG_FILE_ENV: &str = "CHIP8_CONFIG";
+
+/// Default location of the config file, `$XDG_CONFIG_HOME/chip8-emulator/config.toml`,
+/// falling back to `~/.config` when `XDG_CONFIG_HOME` isn't set
+pub fn config_file() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("chip8-emulator").join("config.toml"))
+}
+
+/// The config file and its contents. A file named by `CHIP8_CONFIG` has to be readable,
+/// while the one in the default location is optional
+fn read_config() -> Result<Option<(PathBuf, String)>, String> {
+    if let Some(file) = env::var_os(CONFIG_FILE_ENV).filter(|file| !file.is_empty()) {
+        let file = PathBuf::from(file);
+        let contents = fs::read_to_string(&file)
+            .map_err(|e| format!("Could not read {}: {}", file.display(), e))?;
+        return Ok(Some((file, contents)));
+    }
+    let Some(file) = config_file() else {
+        return Ok(None);
+    };
+    match fs::read_to_string(&file) {
+        Ok(contents) => Ok(Some((file, contents))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => {
+            warn!("Ignoring config file {}: {}", file.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// Back every option of `command` with an environment variable and use the values in
+/// the config file, if there is one, as their defaults
+pub fn layered(command: Command) -> Result<Command, String> {
+    let command = command.mut_args(|arg| {
+        let name = format!("{}{}", ENV_PREFIX, arg.get_id().as_str().to_uppercase());
+        arg.env(name)
+    });
+
+    let Some((file, contents)) = read_config()? else {
+        return Ok(command);
+    };
+    let config: toml::Table = toml::from_str(&contents)
+        .map_err(|e| format!("Could not parse {}: {}", file.display(), e))?;
+    info!("Using config from {}", file.display());
+
+    let mut command = command;
+    for (key, value) in config {
+        // keys can be written like the flags, e.g. `record-scale`, or like `record_scale`
+        let id = key.replace('-', "_");
+        if !command
+            .get_arguments()
+            .any(|arg| arg.get_id() == id.as_str())
+        {
+            warn!("Ignoring unknown option {} in {}", key, file.display());
+            continue;
+        }
+        let value = match value {
+            Value::String(value) => value,
+            Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+            _ => {
+                return Err(format!(
+                    "{} in {} should be a string, number or boolean",
+                    key,
+                    file.display()
+                ))
+            }
+        };
+        command = command.mut_arg(id, |arg| arg.default_value(value));
+    }
+    Ok(command)
+}
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+const FRAMES_PER_SECOND: f64 = 60.0;
+const MAX_FRAMES_PER_UPDATE: u32 = 10;
+// Cap on frames run to catch up, so a stall (e.g. dragging the window) doesn't make
+// the emulator race ahead afterwards
+
+/// How many frames the emulator should run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameBudget {
+    Frames(u32),
+    Unthrottled, // as many frames as can be run in a 60th of a second
+}
+
+/// Pause, frame advance, fast-forward and slow motion. Keeps track of how many 60Hz
+/// frames the emulator should have run based on the wall clock and the current speed.
+/// Timers tick once per emulated frame, so they and the sound scale with the speed
+pub struct Controls {
+    pub paused: bool,
+    pub fast_forward: bool, // held down rather than toggled
+    pub slow_motion: bool,
+    advance_frame: bool,
+    fast_forward_speed: f64, // 0 for unthrottled
+    slow_motion_speed: f64,
+    last_update: Instant,
+    pending_frames: f64, // fractions of a frame carried over between updates
+}
+
+impl Controls {
+    pub fn new(fast_forward_speed: f64, slow_motion_speed: f64) -> Self {
+        Self {
+            paused: false,
+            fast_forward: false,
+            slow_motion: false,
+            advance_frame: false,
+            fast_forward_speed,
+            slow_motion_speed,
+            last_update: Instant::now(),
+            pending_frames: 0.0,
+        }
+    }
+
+    /// Pause if running, run if paused
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Run exactly one frame on the next update, only does anything while paused
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.advance_frame = true;
+        }
+    }
+
+    /// Get how many frames should be run since the last update
+    pub fn frames_due(&mut self) -> FrameBudget {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.last_update = now;
+
+        if self.paused {
+            self.pending_frames = 0.0;
+            let frames = if self.advance_frame { 1 } else { 0 };
+            self.advance_frame = false;
+            return FrameBudget::Frames(frames);
+        }
+
+        let speed = if self.fast_forward {
+            if self.fast_forward_speed <= 0.0 {
+                return FrameBudget::Unthrottled;
+            }
+            self.fast_forward_speed
+        } else if self.slow_motion {
+            self.slow_motion_speed
+        } else {
+            1.0
+        };
+
+        self.pending_frames += elapsed * FRAMES_PER_SECOND * speed;
+        let frames = self.pending_frames.floor();
+        self.pending_frames -= frames;
+        FrameBudget::Frames((frames as u32).min(MAX_FRAMES_PER_UPDATE))
+    }
+}
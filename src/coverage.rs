@@ -0,0 +1,102 @@
+/// Code and data coverage: which bytes of memory were executed as instructions, read as
+/// data by `DXYN` and `FX65`, or written by `FX33` and `FX55`. The map is written as a
+/// line for every run of addresses used the same way:
+///
+/// `200-2A7 executed`
+///
+/// with `read`, `written` or several of them joined by commas, and `untouched` for the
+/// parts of the program that weren't used at all
+use std::fs;
+
+use crate::emulator::{Emulator, MemoryAccess, RAM_SIZE, START_ADDR};
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const WRITTEN: u8 = 4;
+
+/// How each byte of memory has been used
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            flags: vec![0; RAM_SIZE],
+        }
+    }
+
+    /// Mark the instruction at `pc` as executed, and the memory it used as data
+    pub fn record(&mut self, pc: u16, accesses: &[MemoryAccess]) {
+        self.flags[pc as usize % RAM_SIZE] |= EXECUTED;
+        self.flags[(pc as usize + 1) % RAM_SIZE] |= EXECUTED;
+        for access in accesses {
+            match *access {
+                MemoryAccess::Read(address) => self.flags[address as usize % RAM_SIZE] |= READ,
+                MemoryAccess::Write(address) => self.flags[address as usize % RAM_SIZE] |= WRITTEN,
+            }
+        }
+    }
+
+    /// How much of the program was executed, read as data, or not used at all
+    pub fn summary(&self, emu: &Emulator) -> String {
+        let program = self.program(emu);
+        let count = |uses: u8| program.iter().filter(|flags| *flags & uses != 0).count();
+        let percent = |count: usize| count as f64 * 100.0 / program.len().max(1) as f64;
+        let executed = count(EXECUTED);
+        let read = count(READ);
+        let untouched = program.len() - count(EXECUTED | READ | WRITTEN);
+        format!(
+            "{} of {} program bytes executed ({:.1}%), {} read as data ({:.1}%), {} untouched ({:.1}%)",
+            executed,
+            program.len(),
+            percent(executed),
+            read,
+            percent(read),
+            untouched,
+            percent(untouched)
+        )
+    }
+
+    /// Write the summary and the map to `file`
+    pub fn save(&self, file: &str, emu: &Emulator) -> Result<(), String> {
+        let mut map = format!("# {}\n", self.summary(emu));
+        let program_start = START_ADDR as usize;
+        let program_end = program_start + emu.rom().len();
+        let mut start = 0;
+        while start < RAM_SIZE {
+            let flags = self.flags[start];
+            let in_program = (program_start..program_end).contains(&start);
+            let mut end = start;
+            while end + 1 < RAM_SIZE
+                && self.flags[end + 1] == flags
+                && (program_start..program_end).contains(&(end + 1)) == in_program
+            {
+                end += 1;
+            }
+            if flags != 0 || in_program {
+                map += &format!("{:03X}-{:03X} {}\n", start, end, describe(flags));
+            }
+            start = end + 1;
+        }
+        fs::write(file, map).map_err(|e| format!("Could not write {}: {}", file, e))
+    }
+
+    fn program(&self, emu: &Emulator) -> &[u8] {
+        let program_start = START_ADDR as usize;
+        &self.flags[program_start..program_start + emu.rom().len()]
+    }
+}
+
+fn describe(flags: u8) -> String {
+    let uses: Vec<&str> = [(EXECUTED, "executed"), (READ, "read"), (WRITTEN, "written")]
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if uses.is_empty() {
+        "untouched".to_string()
+    } else {
+        uses.join(",")
+    }
+}
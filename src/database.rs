@@ -0,0 +1,233 @@
+/// Looks up ROMs by their SHA-1 hash in a database in the format of the community
+/// CHIP-8 database: https://github.com/chip-8/chip-8-database
+use log::{debug, info, warn};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::emulator::Quirks;
+use crate::keypad::{KeyMap, NUM_KEYS};
+use crate::palette::{parse_color, Palette};
+
+const BUNDLED_PROGRAMS: &str = include_str!("../database/programs.json");
+const BUNDLED_HASHES: &str = include_str!("../database/sha1-hashes.json");
+const BUNDLED_PLATFORMS: &str = include_str!("../database/platforms.json");
+const DATABASE_FILES: [&str; 3] = ["programs.json", "sha1-hashes.json", "platforms.json"];
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    keys: Option<HashMap<String, u8>>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, PlatformQuirks>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    default_tickrate: u32,
+    quirks: PlatformQuirks,
+}
+
+/// Quirks as the database describes them, only the ones this emulator supports.
+/// All optional, since ROMs only list the quirks that differ from their platform
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+struct PlatformQuirks {
+    shift: Option<bool>, // shift VX in place, rather than VY into VX
+    memory_leave_i_unchanged: Option<bool>, // FX55 and FX65 don't change I
+    jump: Option<bool>,  // BXNN jumps to XNN + VX rather than NNN + V0
+}
+
+impl PlatformQuirks {
+    /// These quirks, with any that aren't set taken from `other`
+    fn or(self, other: Self) -> Self {
+        Self {
+            shift: self.shift.or(other.shift),
+            memory_leave_i_unchanged: self
+                .memory_leave_i_unchanged
+                .or(other.memory_leave_i_unchanged),
+            jump: self.jump.or(other.jump),
+        }
+    }
+
+    fn to_quirks(self) -> Quirks {
+        Quirks {
+            use_y_on_shift: !self.shift.unwrap_or(true),
+            use_x_on_jump: self.jump.unwrap_or(false),
+            modify_i_on_load_and_store: !self.memory_leave_i_unchanged.unwrap_or(true),
+        }
+    }
+}
+
+/// What the database knows about a ROM
+#[derive(Debug, Clone)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: String,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+    pub palette: Option<Palette>,
+    pub key_map: Option<KeyMap>,
+}
+
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>, // SHA-1 hash to index in programs
+    platforms: Vec<Platform>,
+}
+
+/// Where the community database is picked up from without `--database`,
+/// `$XDG_DATA_HOME/chip8-emulator/database`, falling back to `~/.local/share`
+/// when `XDG_DATA_HOME` isn't set
+pub fn default_dir() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data_dir.join("chip8-emulator").join("database"))
+}
+
+/// Whether all the database's files are in `dir`. Only some of them being there, e.g. from
+/// an interrupted copy, is warned about, so one copy's hashes aren't paired with another's
+/// programs
+fn is_installed(dir: &Path) -> bool {
+    let files = DATABASE_FILES.map(|name| dir.join(name).is_file());
+    if files.contains(&true) && files.contains(&false) {
+        warn!(
+            "Ignoring the ROM database in {}, it needs all of {}",
+            dir.display(),
+            DATABASE_FILES.join(", ")
+        );
+    }
+    !files.contains(&false)
+}
+
+impl RomDatabase {
+    /// Load the database from `dir` containing `programs.json`, `sha1-hashes.json` and
+    /// `platforms.json`. If None, it's loaded from the default directory if all three
+    /// files are there, and otherwise from the copy bundled with the emulator
+    pub fn load(dir: Option<&str>) -> Result<Self, String> {
+        let dir = match dir {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => default_dir().filter(|dir| is_installed(dir)),
+        };
+        let read = |name: &str, bundled: &'static str| -> Result<String, String> {
+            let Some(dir) = &dir else {
+                return Ok(bundled.to_string());
+            };
+            let file = dir.join(name);
+            info!("Loading ROM database file {}", file.display());
+            fs::read_to_string(&file)
+                .map_err(|e| format!("Could not read {}: {}", file.display(), e))
+        };
+        let parse_error =
+            |name: &str, e: serde_json::Error| format!("Could not parse {}: {}", name, e);
+
+        let database = Self {
+            programs: serde_json::from_str(&read("programs.json", BUNDLED_PROGRAMS)?)
+                .map_err(|e| parse_error("programs.json", e))?,
+            hashes: serde_json::from_str(&read("sha1-hashes.json", BUNDLED_HASHES)?)
+                .map_err(|e| parse_error("sha1-hashes.json", e))?,
+            platforms: serde_json::from_str(&read("platforms.json", BUNDLED_PLATFORMS)?)
+                .map_err(|e| parse_error("platforms.json", e))?,
+        };
+        if database.hashes.is_empty() {
+            let install_dir = default_dir().unwrap_or_default();
+            warn!(
+                "The ROM database is empty, so ROMs run with the default settings. Copy the \
+                 community database's JSON files into {} to look them up",
+                install_dir.display()
+            );
+        }
+        Ok(database)
+    }
+
+    /// Find the ROM with the same SHA-1 hash as `rom`
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        let hash = format!("{:x}", Sha1::digest(rom));
+        let Some(program) = self.hashes.get(&hash).and_then(|&i| self.programs.get(i)) else {
+            debug!("ROM with hash {} is not in the database", hash);
+            return None;
+        };
+        let rom = program.roms.get(&hash)?;
+
+        // ROMs list the platforms they run on best first
+        let Some(platform) = rom
+            .platforms
+            .iter()
+            .find_map(|id| self.platforms.iter().find(|p| p.id == *id))
+        else {
+            warn!(
+                "{} only runs on platforms that aren't known: {:?}",
+                program.title, rom.platforms
+            );
+            return None;
+        };
+        let quirks = rom
+            .quirky_platforms
+            .get(&platform.id)
+            .copied()
+            .unwrap_or_default()
+            .or(platform.quirks);
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platform: platform.id.clone(),
+            quirks: quirks.to_quirks(),
+            instructions_per_frame: rom.tickrate.unwrap_or(platform.default_tickrate),
+            palette: rom
+                .colors
+                .as_ref()
+                .and_then(|colors| palette(&colors.pixels)),
+            key_map: rom.keys.as_ref().map(key_map),
+        })
+    }
+}
+
+/// Palette from the first two pixel colours, for pixels that are off and on
+fn palette(pixels: &[String]) -> Option<Palette> {
+    match pixels {
+        [background, foreground, ..] => Some(Palette {
+            background: parse_color(background).ok()?,
+            foreground: parse_color(foreground).ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Key map from the database's names for the keys a game uses
+fn key_map(keys: &HashMap<String, u8>) -> KeyMap {
+    let key = |name: &str| keys.get(name).copied().filter(|&k| (k as usize) < NUM_KEYS);
+    KeyMap {
+        up: key("up"),
+        down: key("down"),
+        left: key("left"),
+        right: key("right"),
+        a: key("a"),
+        b: key("b"),
+    }
+}
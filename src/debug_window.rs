@@ -0,0 +1,76 @@
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+use std::time::{Duration, Instant};
+
+/// Debug windows don't need redrawing at the full 60Hz
+const REFRESH_INTERVAL: Duration = Duration::from_millis(1000 / 30);
+
+pub const BACKGROUND_COLOR: Color = Color::RGB(0x10, 0x10, 0x18);
+pub const TEXT_COLOR: Color = Color::RGB(0xC0, 0xC0, 0xC0);
+pub const HIGHLIGHT_COLOR: Color = Color::RGB(0xFF, 0xD7, 0x00);
+
+/// A secondary window for looking inside the emulator, only created while it's open
+pub struct DebugWindow {
+    title: &'static str,
+    width: u32,
+    height: u32,
+    canvas: Option<Canvas<Window>>,
+    last_draw: Instant,
+}
+
+impl DebugWindow {
+    pub fn new(title: &'static str, width: u32, height: u32) -> Self {
+        Self {
+            title,
+            width,
+            height,
+            canvas: None,
+            last_draw: Instant::now(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.canvas.is_some()
+    }
+
+    /// Tells if events for the window with `window_id` belong to this window
+    pub fn owns(&self, window_id: u32) -> bool {
+        self.canvas
+            .as_ref()
+            .is_some_and(|canvas| canvas.window().id() == window_id)
+    }
+
+    /// Open the window if it's closed, close it if it's open
+    pub fn toggle(&mut self, video: &VideoSubsystem) -> Result<(), String> {
+        if self.is_open() {
+            self.close();
+            return Ok(());
+        }
+        let window = video
+            .window(self.title, self.width, self.height)
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.canvas = Some(window.into_canvas().build().map_err(|e| e.to_string())?);
+        self.last_draw = Instant::now() - REFRESH_INTERVAL;
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        self.canvas = None;
+    }
+
+    /// The canvas to draw on if the window is open and it's time to redraw it, already
+    /// cleared to the background colour. Call `present` on it once drawn
+    pub fn canvas_to_draw(&mut self) -> Option<&mut Canvas<Window>> {
+        let canvas = self.canvas.as_mut()?;
+        if self.last_draw.elapsed() < REFRESH_INTERVAL {
+            return None;
+        }
+        self.last_draw = Instant::now();
+        canvas.set_draw_color(BACKGROUND_COLOR);
+        canvas.clear();
+        Some(canvas)
+    }
+}
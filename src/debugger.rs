@@ -0,0 +1,438 @@
+use log::{error, info};
+use std::collections::BTreeMap;
+
+use crate::analyzer::Analyzer;
+use crate::coverage::Coverage;
+use crate::emulator::{Emulator, RAM_SIZE};
+use crate::expression::Expression;
+use crate::profiler::Profiler;
+use crate::symbols::Symbols;
+use crate::tracer::Tracer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    Running,
+    Halted,
+    Stepping,     // run one instruction, then halt
+    SteppingBack, // undo one instruction, then halt
+    RunningBack,  // undo instructions until a breakpoint, watchpoint or the start of history
+}
+
+/// Why the debugger last halted the emulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Requested,    // halted from outside, e.g. on attaching or an interrupt
+    Breakpoint,   // reached an address with a breakpoint, and its condition was true
+    Watchpoint,   // the value of a watched expression changed
+    Step,         // finished a single step
+    Fault,        // an instruction couldn't run, like a return with an empty stack
+    HistoryStart, // stepped back as far as the history goes
+}
+
+/// A breakpoint, that only stops the emulator if its condition is true
+struct Breakpoint {
+    condition: Option<Expression>,
+    hits: u64, // times the address has been reached, whether the condition was true or not
+}
+
+/// An expression that stops the emulator when its value changes
+struct Watchpoint {
+    expression: Expression,
+    value: Option<i64>, // None until the first instruction after it was added
+}
+
+/// Runs the emulator an instruction at a time so it can be halted between any two
+/// instructions, at breakpoints, when watched values change or after single steps
+pub struct Debugger {
+    state: RunState,
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    stop_reason: StopReason,
+    resuming: bool, // don't stop at a breakpoint on the instruction execution resumes from
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    analyzer: Option<Analyzer>,
+    symbols: Symbols,
+}
+
+impl Debugger {
+    /// A debugger that doesn't stop the emulator until it's told to, or starts `halted`
+    pub fn new(halted: bool) -> Self {
+        Self {
+            state: if halted {
+                RunState::Halted
+            } else {
+                RunState::Running
+            },
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            stop_reason: StopReason::Requested,
+            resuming: false,
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            analyzer: None,
+            symbols: Symbols::default(),
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.state == RunState::Halted
+    }
+
+    /// Tells if a single step, or stepping or running backwards, has been asked for and
+    /// not run yet
+    pub fn is_stepping(&self) -> bool {
+        matches!(
+            self.state,
+            RunState::Stepping | RunState::SteppingBack | RunState::RunningBack
+        )
+    }
+
+    pub fn stop_reason(&self) -> StopReason {
+        self.stop_reason
+    }
+
+    pub fn halt(&mut self) {
+        self.stop(StopReason::Requested);
+    }
+
+    /// Carry on running until the next breakpoint
+    pub fn resume(&mut self) {
+        self.state = RunState::Running;
+        self.resuming = true;
+    }
+
+    /// Run a single instruction and halt again
+    pub fn step(&mut self) {
+        self.state = RunState::Stepping;
+    }
+
+    /// Undo the last instruction and halt again, needs the emulator to keep history
+    pub fn step_back(&mut self) {
+        self.state = RunState::SteppingBack;
+    }
+
+    /// Undo instructions until a breakpoint or a watchpoint, or the start of the history
+    pub fn run_back(&mut self) {
+        self.state = RunState::RunningBack;
+    }
+
+    /// Label addresses in backtraces with `symbols`
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    #[cfg(feature = "sdl")]
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// Where the emulator is and the calls that led there, the most recent first
+    pub fn backtrace(&self, emu: &Emulator) -> String {
+        self.symbols.backtrace(emu.pc(), emu.stack()).join(" <- ")
+    }
+
+    /// Stop at `address`, only when `condition` is true if there is one. Replaces any
+    /// breakpoint that was already there
+    pub fn add_breakpoint(&mut self, address: u16, condition: Option<Expression>) {
+        self.breakpoints
+            .insert(address, Breakpoint { condition, hits: 0 });
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Stop whenever the value of `expression` changes
+    pub fn add_watchpoint(&mut self, expression: Expression) {
+        self.watchpoints.push(Watchpoint {
+            expression,
+            value: None,
+        });
+    }
+
+    /// Run a command typed into the debugger, GDB's `monitor` command, and return what
+    /// to show for it:
+    ///
+    /// - `break ADDRESS [if CONDITION]` adds a breakpoint
+    /// - `watch EXPRESSION` adds a watchpoint
+    /// - `delete` removes all breakpoints and watchpoints
+    /// - `info` lists them, with the hits of each breakpoint and the value of each watchpoint
+    /// - `backtrace` shows the PC and the calls that led to it
+    pub fn command(&mut self, command: &str, emu: &Emulator) -> Result<String, String> {
+        let command = command.trim();
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        match name {
+            "break" => {
+                let (address, condition) = parse_breakpoint(args)?;
+                let reply = match &condition {
+                    Some(condition) => format!("Breakpoint at {:03X} if {}", address, condition),
+                    None => format!("Breakpoint at {:03X}", address),
+                };
+                self.add_breakpoint(address, condition);
+                Ok(reply)
+            }
+            "watch" => {
+                let expression = Expression::parse(args)?;
+                let reply = format!("Watching {}", expression);
+                self.add_watchpoint(expression);
+                Ok(reply)
+            }
+            "delete" => {
+                self.breakpoints.clear();
+                self.watchpoints.clear();
+                Ok("Deleted all breakpoints and watchpoints".to_string())
+            }
+            "info" => Ok(self.describe()),
+            "backtrace" | "bt" => Ok(self.backtrace(emu)),
+            _ => Err(format!(
+                "Unknown command {}, try break, watch, delete, info or backtrace",
+                name
+            )),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        for (address, breakpoint) in &self.breakpoints {
+            let condition = match &breakpoint.condition {
+                Some(condition) => format!(" if {}", condition),
+                None => String::new(),
+            };
+            lines.push(format!(
+                "Breakpoint at {:03X}{}, hit {} times",
+                address, condition, breakpoint.hits
+            ));
+        }
+        for watchpoint in &self.watchpoints {
+            let value = match watchpoint.value {
+                Some(value) => format!("{:#X}", value),
+                None => "not read yet".to_string(),
+            };
+            lines.push(format!("Watching {} = {}", watchpoint.expression, value));
+        }
+        if lines.is_empty() {
+            "No breakpoints or watchpoints".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    /// Write a line to `tracer` for every instruction from now on
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Count the instructions executed from now on in `profiler`
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Map the memory executed, read and written from now on in `coverage`
+    pub fn set_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Look out for suspicious behaviour with `analyzer` from now on
+    pub fn set_analyzer(&mut self, analyzer: Analyzer) {
+        self.analyzer = Some(analyzer);
+    }
+
+    pub fn analyzer(&self) -> Option<&Analyzer> {
+        self.analyzer.as_ref()
+    }
+
+    /// Remove all breakpoints and watchpoints and let the emulator run freely
+    pub fn detach(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.resume();
+    }
+
+    /// Run the rest of the current frame, unless the emulator is halted or halts on the way.
+    /// Returns true if a frame was finished
+    pub fn run_frame(&mut self, emu: &mut Emulator, instructions_per_frame: u32) -> bool {
+        match self.state {
+            RunState::Halted => false,
+            RunState::Stepping => {
+                let finished = self.step_instruction(emu, instructions_per_frame);
+                self.watchpoint_changed(emu);
+                if !self.faulted(emu) {
+                    self.stop(StopReason::Step);
+                }
+                finished
+            }
+            RunState::SteppingBack => {
+                if emu.step_back() {
+                    self.watchpoint_changed(emu);
+                    self.stop(StopReason::Step);
+                } else {
+                    self.stop(StopReason::HistoryStart);
+                }
+                false
+            }
+            RunState::RunningBack => {
+                self.run_back_to_stop(emu);
+                false
+            }
+            RunState::Running => loop {
+                if !self.resuming && self.breakpoint_hit(emu) {
+                    self.stop(StopReason::Breakpoint);
+                    return false;
+                }
+                self.resuming = false;
+                let finished = self.step_instruction(emu, instructions_per_frame);
+                if self.faulted(emu) {
+                    return finished;
+                }
+                if self.watchpoint_changed(emu) {
+                    info!("Backtrace: {}", self.backtrace(emu));
+                    self.stop(StopReason::Watchpoint);
+                    return finished;
+                }
+                if finished {
+                    return true;
+                }
+            },
+        }
+    }
+
+    /// Undo instructions until one at a breakpoint whose condition is true, a watched value
+    /// changes, or the history runs out. Hits aren't counted going backwards
+    fn run_back_to_stop(&mut self, emu: &mut Emulator) {
+        while emu.step_back() {
+            if self.watchpoint_changed(emu) {
+                self.stop(StopReason::Watchpoint);
+                return;
+            }
+            if let Some(breakpoint) = self.breakpoints.get(&emu.pc()) {
+                let hit = match &breakpoint.condition {
+                    Some(condition) => condition.evaluate(emu, breakpoint.hits) != 0,
+                    None => true,
+                };
+                if hit {
+                    info!("Breakpoint hit at {:#05X} running backwards", emu.pc());
+                    self.stop(StopReason::Breakpoint);
+                    return;
+                }
+            }
+        }
+        info!("Reached the start of the history");
+        self.stop(StopReason::HistoryStart);
+    }
+
+    /// Count a hit if there's a breakpoint at the PC, and tell if it should stop
+    fn breakpoint_hit(&mut self, emu: &Emulator) -> bool {
+        let pc = emu.pc();
+        let Some(breakpoint) = self.breakpoints.get_mut(&pc) else {
+            return false;
+        };
+        breakpoint.hits += 1;
+        let hit = match &breakpoint.condition {
+            Some(condition) => condition.evaluate(emu, breakpoint.hits) != 0,
+            None => true,
+        };
+        if hit {
+            info!(
+                "Breakpoint hit at {:#05X}, hit count {}",
+                pc, breakpoint.hits
+            );
+            info!("Backtrace: {}", self.backtrace(emu));
+        }
+        hit
+    }
+
+    /// Stop if the last instruction couldn't run, telling if it couldn't
+    fn faulted(&mut self, emu: &mut Emulator) -> bool {
+        let Some(fault) = emu.take_fault() else {
+            return false;
+        };
+        error!("{}, backtrace: {}", fault, self.backtrace(emu));
+        self.stop(StopReason::Fault);
+        true
+    }
+
+    /// Update the value of every watchpoint, telling if any of them changed
+    fn watchpoint_changed(&mut self, emu: &Emulator) -> bool {
+        let mut changed = false;
+        for watchpoint in self.watchpoints.iter_mut() {
+            let value = watchpoint.expression.evaluate(emu, 0);
+            if let Some(previous) = watchpoint.value.filter(|previous| *previous != value) {
+                info!(
+                    "{} changed from {:#X} to {:#X} before {:#05X}",
+                    watchpoint.expression,
+                    previous,
+                    value,
+                    emu.pc()
+                );
+                changed = true;
+            }
+            watchpoint.value = Some(value);
+        }
+        changed
+    }
+
+    /// Execute one instruction, tracing, profiling, mapping the coverage of and analyzing
+    /// it if asked to. Returns true if it finished a frame
+    fn step_instruction(&mut self, emu: &mut Emulator, instructions_per_frame: u32) -> bool {
+        let pc = emu.pc();
+        let opcode = emu.instruction_at(pc);
+        let entry = self.tracer.as_ref().and_then(|tracer| tracer.before(emu));
+        for watchpoint in self.watchpoints.iter_mut() {
+            if watchpoint.value.is_none() {
+                watchpoint.value = Some(watchpoint.expression.evaluate(emu, 0));
+            }
+        }
+        if let Some(analyzer) = self.analyzer.as_mut() {
+            analyzer.before(emu);
+        }
+        let finished = emu.step(instructions_per_frame);
+
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, opcode, finished);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(pc, emu.last_accesses());
+        }
+        if let Some(analyzer) = self.analyzer.as_mut() {
+            analyzer.after(pc, opcode, emu);
+        }
+        if let (Some(tracer), Some(entry)) = (self.tracer.as_mut(), entry) {
+            if let Err(e) = tracer.after(entry, emu) {
+                error!("Stopped tracing, could not write the trace: {}", e);
+                self.tracer = None;
+            }
+        }
+        finished
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        self.state = RunState::Halted;
+        self.stop_reason = reason;
+    }
+}
+
+/// Parse a breakpoint, an address in hex with an optional condition after `if`, e.g.
+/// `2A0 if V3 == 0x10`
+pub fn parse_breakpoint(breakpoint: &str) -> Result<(u16, Option<Expression>), String> {
+    let breakpoint = breakpoint.trim();
+    let (address, condition) = match breakpoint.split_once(" if ") {
+        Some((address, condition)) => (address, Some(Expression::parse(condition)?)),
+        None => (breakpoint, None),
+    };
+    let address = u16::from_str_radix(address.trim().trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|address| (*address as usize) < RAM_SIZE)
+        .ok_or_else(|| format!("{} is not an address like 2A0", address))?;
+    Ok((address, condition))
+}
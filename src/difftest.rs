@@ -0,0 +1,211 @@
+/// Differential testing: runs a ROM and checks every instruction against a trace from
+/// another emulator, in the format `--trace` writes. Only the PC, opcode, `VX=..` and `I=..`
+/// are read from each line of the reference, the cycle and the assembly are ignored, and
+/// registers that aren't listed are taken to be unchanged
+use std::fs;
+
+use log::{error, info};
+
+use crate::emulator::{Emulator, NUM_VARIABLE_REGISTERS};
+use crate::movie::Movie;
+use crate::opcode::Opcode;
+
+/// State after an instruction, as much of it as traces show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    pc: u16, // address the instruction was at
+    opcode: u16,
+    registers: [u8; NUM_VARIABLE_REGISTERS],
+    i: u16,
+}
+
+impl State {
+    /// Names of the parts of the state that differ from `other`
+    fn differences(&self, other: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.pc != other.pc {
+            differences.push("PC".to_string());
+        }
+        if self.opcode != other.opcode {
+            differences.push("OPCODE".to_string());
+        }
+        for reg in 0..NUM_VARIABLE_REGISTERS {
+            if self.registers[reg] != other.registers[reg] {
+                differences.push(format!("V{:X}", reg));
+            }
+        }
+        if self.i != other.i {
+            differences.push("I".to_string());
+        }
+        differences
+    }
+
+    fn describe(&self) -> String {
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(reg, value)| format!("V{:X}={:02X}", reg, value))
+            .collect();
+        format!(
+            "{:03X} {:04X} {:<18} {} I={:03X}",
+            self.pc,
+            self.opcode,
+            Opcode::decode(self.opcode).to_string(),
+            registers.join(" "),
+            self.i
+        )
+    }
+}
+
+/// Run `emu` an instruction at a time against the trace in `reference_file`, replaying
+/// `movie` if given, until the reference ends or the first instruction that differs.
+/// Both states are logged as errors if they differ
+pub fn compare_trace(
+    emu: &mut Emulator,
+    instructions_per_frame: u32,
+    mut movie: Option<Movie>,
+    reference_file: &str,
+) -> Result<(), String> {
+    let reference = fs::read_to_string(reference_file)
+        .map_err(|e| format!("Could not read {}: {}", reference_file, e))?;
+
+    let mut frame = 0;
+    if let Some(movie) = movie.as_mut() {
+        movie.apply(frame, &mut emu.keypad);
+    }
+    let mut expected_registers = [0; NUM_VARIABLE_REGISTERS];
+    let mut previous: Option<State> = None;
+    let mut compared = 0;
+
+    for (number, line) in reference.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let expected = parse_line(line, &mut expected_registers).ok_or_else(|| {
+            format!(
+                "{} line {} is not a trace line: {}",
+                reference_file,
+                number + 1,
+                line
+            )
+        })?;
+
+        let pc = emu.pc();
+        let opcode = emu.instruction_at(pc);
+        let finished_frame = emu.step(instructions_per_frame);
+        let actual = State {
+            pc,
+            opcode,
+            registers: *emu.registers(),
+            i: emu.i(),
+        };
+
+        if actual != expected {
+            error!(
+                "Diverged at cycle {} (line {} of {}), frame {}",
+                emu.cycles(),
+                number + 1,
+                reference_file,
+                frame
+            );
+            if let Some(previous) = previous {
+                error!("  last match: {}", previous.describe());
+            }
+            error!("  expected:   {}", expected.describe());
+            error!("  emulator:   {}", actual.describe());
+            error!("  differs in: {}", actual.differences(&expected).join(", "));
+            return Err(format!(
+                "Trace diverged from {} after {} matching instructions",
+                reference_file, compared
+            ));
+        }
+
+        previous = Some(actual);
+        compared += 1;
+        if finished_frame {
+            frame += 1;
+            if let Some(movie) = movie.as_mut() {
+                movie.apply(frame, &mut emu.keypad);
+            }
+        }
+    }
+
+    info!(
+        "Matched all {} instructions of {}",
+        compared, reference_file
+    );
+    Ok(())
+}
+
+/// Parse a reference trace line, updating `registers` with the ones it changed
+fn parse_line(line: &str, registers: &mut [u8; NUM_VARIABLE_REGISTERS]) -> Option<State> {
+    let mut parts = line.split_whitespace();
+    let _cycle = parts.next()?;
+    let pc = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let opcode = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let mut i = None;
+    for part in parts {
+        let Some((name, value)) = part.split_once('=') else {
+            continue; // part of the assembly
+        };
+        match name.strip_prefix('V') {
+            Some(reg) => {
+                let reg = usize::from_str_radix(reg, 16).ok()?;
+                *registers.get_mut(reg)? = u8::from_str_radix(value, 16).ok()?;
+            }
+            None if name == "I" => i = Some(u16::from_str_radix(value, 16).ok()?),
+            None => {}
+        }
+    }
+    Some(State {
+        pc,
+        opcode,
+        registers: *registers,
+        i: i?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let mut registers = [0; NUM_VARIABLE_REGISTERS];
+        registers[3] = 0x33;
+        let state = parse_line(
+            "        42 208 7001   ADD V0, 0x01       V0=05 I=2A0",
+            &mut registers,
+        )
+        .unwrap();
+        assert_eq!((state.pc, state.opcode, state.i), (0x208, 0x7001, 0x2A0));
+        assert_eq!(state.registers[0], 0x05);
+        assert_eq!(
+            state.registers[3], 0x33,
+            "registers that aren't listed are unchanged"
+        );
+
+        let state = parse_line("43 20A 8F10 LD VF, V1 VF=05 VA=1 I=2A0", &mut registers).unwrap();
+        assert_eq!(state.registers[0], 0x05);
+        assert_eq!((state.registers[0xA], state.registers[0xF]), (0x01, 0x05));
+        assert_eq!(state.describe().get(..13), Some("20A 8F10 LD V"));
+    }
+
+    #[test]
+    fn bad_lines() {
+        let mut registers = [0; NUM_VARIABLE_REGISTERS];
+        for line in [
+            "42 208",
+            "42 xyz 7001 V0=05 I=2A0",
+            "42 208 7001 V0=05",
+            "42 208 7001 VG=05 I=2A0",
+            "42 208 7001 V10=05 I=2A0",
+            "42 208 7001 V0=100 I=2A0",
+            "42 208 7001 V0=05 I=zz",
+        ] {
+            assert_eq!(parse_line(line, &mut registers), None, "{}", line);
+        }
+    }
+}
@@ -1,8 +1,9 @@
-use log::{debug, error, info, warn};
+use log::warn;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
 use std::fs;
 
-use std::time::{Duration, Instant};
-
 use crate::keypad::{Keypad, NUM_KEYS};
 use crate::opcode::Opcode;
 
@@ -10,13 +11,16 @@ pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 // CHIP8 screen size is 64*32 pixels
 
-const RAM_SIZE: usize = 4096;
+pub const RAM_SIZE: usize = 4096;
 // CHIP8 memory size is 4 kilobytes
 
-const NUM_VARIABLE_REGISTERS: usize = 16;
+pub const NUM_VARIABLE_REGISTERS: usize = 16;
 // 16 variable registers in CHIP8
 
-const FONT_SET_SIZE: usize = 80;
+pub const FONT_SET_SIZE: usize = 80;
+
+pub const FONT_HEIGHT: usize = 5;
+// each of the 16 hex digits in the font is 5 bytes tall
 
 const FONT_SET: [u8; FONT_SET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -37,6 +41,58 @@ const FONT_SET: [u8; FONT_SET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// A byte of memory an instruction read or wrote as data, rather than ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read(u16),
+    Write(u16),
+}
+
+/// What an instruction changed, to put back when stepping backwards
+struct Undo {
+    pc: u16,
+    i: u16,
+    registers: [u8; NUM_VARIABLE_REGISTERS],
+    stack_len: usize,
+    stack_top: Option<u16>, // the return address a `00EE` pops
+    delay_timer: u8,
+    sound_timer: u8,
+    cycles: u64,
+    frame_cycles: u32,
+    rng: Option<StdRng>,                             // only kept for `CXNN`
+    ram: Vec<(usize, u8, u64)>,                      // address, old value and old write cycle
+    screen_rows: Vec<(usize, [bool; SCREEN_WIDTH])>, // old rows, in the order they changed
+}
+
+/// Behaviours that differ between CHIP8 implementations, see the flags in `main.rs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    pub use_y_on_shift: bool,
+    pub use_x_on_jump: bool,
+    pub modify_i_on_load_and_store: bool,
+}
+
+impl Quirks {
+    /// Short description of the quirks that are turned on, for showing to the user
+    pub fn label(&self) -> String {
+        let mut enabled: Vec<&str> = Vec::new();
+        if self.use_y_on_shift {
+            enabled.push("SHIFT VY");
+        }
+        if self.use_x_on_jump {
+            enabled.push("JUMP VX");
+        }
+        if self.modify_i_on_load_and_store {
+            enabled.push("LOAD/STORE I");
+        }
+        if enabled.is_empty() {
+            "DEFAULT".to_string()
+        } else {
+            enabled.join(", ")
+        }
+    }
+}
+
 pub struct Emulator {
     ram: [u8; RAM_SIZE],
     screen: [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT], // bool because pixels can be either black or white
@@ -49,21 +105,25 @@ pub struct Emulator {
     variable_registers: [u8; NUM_VARIABLE_REGISTERS],
     pub keypad: Keypad,
     redraw_required: bool, // flag indicating a change to the screen was made
-    use_y_on_shift: bool,
-    use_x_on_jump: bool,
-    modify_i_on_load_and_store: bool,
-    last_timer_update: Instant,
+    quirks: Quirks,
+    cycles: u64,       // number of instructions executed since the emulator was created
+    frame_cycles: u32, // instructions executed so far in the current 60Hz frame
+    rom: Vec<u8>,      // copy of the loaded program, so it can be reloaded on reset
+    seed: Option<u64>, // seed for random numbers, picked randomly on every reset if None
+    rng: StdRng,
+    write_cycles: Vec<u64>, // cycle of the last write to each address by the program, 0 if never
+    accesses: Vec<MemoryAccess>, // data read and written by the last instruction
+    fault: Option<String>,  // why the last instruction couldn't run, if it couldn't
+    history: VecDeque<Undo>, // how to undo each of the last instructions, the latest last
+    history_limit: usize,   // instructions to keep in the history, 0 to not keep any
+    undo: Option<Undo>,     // how to undo the instruction being executed
 }
 
-const START_ADDR: u16 = 0x200;
+pub const START_ADDR: u16 = 0x200;
 // CHIP8 programs are supposed to be loaded into memory after address 200
 
 impl Emulator {
-    pub fn new(
-        use_y_on_shift: bool,
-        use_x_on_jump: bool,
-        modify_i_on_load_and_store: bool,
-    ) -> Self {
+    pub fn new(quirks: Quirks, seed: Option<u64>) -> Self {
         let mut emulator: Self = Self {
             ram: [0; RAM_SIZE],
             screen: [[false; SCREEN_WIDTH]; SCREEN_HEIGHT],
@@ -76,10 +136,18 @@ impl Emulator {
             variable_registers: [0; NUM_VARIABLE_REGISTERS],
             keypad: Keypad::new(),
             redraw_required: false,
-            use_y_on_shift,
-            use_x_on_jump,
-            modify_i_on_load_and_store,
-            last_timer_update: Instant::now(),
+            quirks,
+            cycles: 0,
+            frame_cycles: 0,
+            rom: Vec::new(),
+            seed,
+            rng: new_rng(seed),
+            write_cycles: vec![0; RAM_SIZE],
+            accesses: Vec::new(),
+            fault: None,
+            history: VecDeque::new(),
+            history_limit: 0,
+            undo: None,
         };
 
         emulator.ram[..FONT_SET_SIZE].copy_from_slice(&FONT_SET);
@@ -87,11 +155,54 @@ impl Emulator {
     }
 
     /// Load program into memory from the specified file
-    pub fn load_file(&mut self, file: &str) {
-        let program_bytes = fs::read(file).unwrap();
-        for (i, byte) in (program_bytes).iter().enumerate() {
-            self.ram[START_ADDR as usize + i] = *byte;
+    pub fn load_file(&mut self, file: &str) -> Result<(), String> {
+        let program_bytes =
+            fs::read(file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+        self.load_rom(program_bytes)
+            .map_err(|e| format!("Could not load {}: {}", file, e))
+    }
+
+    /// Replace the program with `program_bytes` and hard reset, so it starts from a clean slate
+    pub fn load_rom(&mut self, program_bytes: Vec<u8>) -> Result<(), String> {
+        if program_bytes.len() > RAM_SIZE - START_ADDR as usize {
+            return Err(format!(
+                "program is {} bytes, too big to fit in memory",
+                program_bytes.len()
+            ));
         }
+        self.rom = program_bytes;
+        self.hard_reset();
+        Ok(())
+    }
+
+    /// Restart the program: clears the registers, stack, timers, screen, cycle count and
+    /// history, copies the program back into memory and re-seeds the random numbers.
+    /// Memory outside the program is left as it was
+    pub fn reset(&mut self) {
+        self.screen = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        self.pc = START_ADDR;
+        self.i = 0;
+        self.stack.clear();
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.variable_registers = [0; NUM_VARIABLE_REGISTERS];
+        self.cycles = 0;
+        self.frame_cycles = 0;
+        self.rng = new_rng(self.seed);
+        let program_area = START_ADDR as usize..START_ADDR as usize + self.rom.len();
+        self.ram[program_area].copy_from_slice(&self.rom);
+        self.write_cycles.fill(0);
+        self.accesses.clear();
+        self.fault = None;
+        self.history.clear();
+        self.undo = None;
+        self.redraw_required = true;
+    }
+
+    /// Restart the program like `reset`, but also clear all memory other than the font
+    pub fn hard_reset(&mut self) {
+        self.ram[FONT_SET_SIZE..].fill(0);
+        self.reset();
     }
 
     /// Tells if the emulator needs a redraw, automatically updated the redraw required
@@ -104,22 +215,213 @@ impl Emulator {
         false
     }
 
-    /// Handles updating of sound, and delay timer 60 times per second
-    fn handle_timers(&mut self) {
-        let now = Instant::now();
-        let timer_update_delta = Duration::from_secs_f64(1.0 / 60.0);
-        if now.duration_since(self.last_timer_update) >= timer_update_delta {
-            self.delay_timer = self.delay_timer.saturating_sub(1);
-            self.sound_timer = self.sound_timer.saturating_sub(1);
-            self.last_timer_update = now;
+    /// Execute a single instruction, updating the timers if it's the last of a frame of
+    /// `instructions_per_frame` instructions. Returns true if it finished the frame
+    pub fn step(&mut self, instructions_per_frame: u32) -> bool {
+        if self.history_limit > 0 {
+            self.undo = Some(self.new_undo());
+        }
+        self.execute();
+        self.frame_cycles += 1;
+        let finished = self.frame_cycles >= instructions_per_frame;
+        if finished {
+            self.frame_cycles = 0;
+            self.tick_timers();
+        }
+        if let Some(undo) = self.undo.take() {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
+            }
+            self.history.push_back(undo);
+        }
+        finished
+    }
+
+    /// Keep what's needed to undo the last `limit` instructions stepped, so they can be
+    /// stepped back through. 0 stops keeping any
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    /// Undo the last instruction stepped, putting everything it changed back. Returns false
+    /// if there's no history left to undo
+    pub fn step_back(&mut self) -> bool {
+        let Some(undo) = self.history.pop_back() else {
+            return false;
+        };
+        for (address, value, cycle) in undo.ram.into_iter().rev() {
+            self.ram[address] = value;
+            self.write_cycles[address] = cycle;
+        }
+        for (y, row) in undo.screen_rows.into_iter().rev() {
+            self.screen[y] = row;
+        }
+        self.pc = undo.pc;
+        self.i = undo.i;
+        self.variable_registers = undo.registers;
+        self.stack.truncate(undo.stack_len);
+        if let (true, Some(top)) = (self.stack.len() < undo.stack_len, undo.stack_top) {
+            self.stack.push(top);
+        }
+        self.delay_timer = undo.delay_timer;
+        self.sound_timer = undo.sound_timer;
+        self.cycles = undo.cycles;
+        self.frame_cycles = undo.frame_cycles;
+        if let Some(rng) = undo.rng {
+            self.rng = rng;
+        }
+        self.accesses.clear();
+        self.fault = None;
+        self.redraw_required = true;
+        true
+    }
+
+    /// How to undo the next instruction, apart from the memory and screen it changes,
+    /// which are added as it runs
+    fn new_undo(&self) -> Undo {
+        let random = self.instruction_at(self.pc) >> 12 == 0xC;
+        Undo {
+            pc: self.pc,
+            i: self.i,
+            registers: self.variable_registers,
+            stack_len: self.stack.len(),
+            stack_top: self.stack.last().copied(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            cycles: self.cycles,
+            frame_cycles: self.frame_cycles,
+            rng: random.then(|| self.rng.clone()),
+            ram: Vec::new(),
+            screen_rows: Vec::new(),
+        }
+    }
+
+    /// Remember row `y` of the screen before it changes, if keeping history
+    fn save_screen_row(&mut self, y: usize) {
+        if let Some(undo) = self.undo.as_mut() {
+            if y < SCREEN_HEIGHT && !undo.screen_rows.iter().any(|(row, _)| *row == y) {
+                undo.screen_rows.push((y, self.screen[y]));
+            }
         }
     }
 
+    /// Decrement the sound and delay timers once, every 60Hz frame ticks the timers exactly once
+    fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
     /// Returns the current state of the sound timer
     pub fn sound_timer(&self) -> &u8 {
         &self.sound_timer
     }
 
+    /// Returns the current state of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Returns the quirks the emulator is running with
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// Change the quirks the emulator runs with
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Returns the program that was loaded
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Returns the number of instructions executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the whole of memory
+    pub fn ram(&self) -> &[u8; RAM_SIZE] {
+        &self.ram
+    }
+
+    /// Change the byte at `address`, for editing memory from a debugger
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.ram[address as usize % RAM_SIZE] = value;
+    }
+
+    /// Returns the cycle each address was last written to by the program, 0 if it never was
+    #[cfg(feature = "sdl")]
+    pub fn write_cycles(&self) -> &[u64] {
+        &self.write_cycles
+    }
+
+    /// Returns the memory the last instruction read or wrote as data, in the order it did
+    pub fn last_accesses(&self) -> &[MemoryAccess] {
+        &self.accesses
+    }
+
+    /// Returns why the last instruction couldn't run, if it couldn't, and forgets it.
+    /// The PC is left at the instruction, so it faults again if run again
+    pub fn take_fault(&mut self) -> Option<String> {
+        self.fault.take()
+    }
+
+    /// Returns the address of the next instruction
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the value of the index register
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    /// Returns the variable registers V0 to VF
+    pub fn registers(&self) -> &[u8; NUM_VARIABLE_REGISTERS] {
+        &self.variable_registers
+    }
+
+    /// Change the value of register V`reg`
+    pub fn set_register(&mut self, reg: usize, value: u8) {
+        self.variable_registers[reg] = value;
+    }
+
+    /// Change the value of the index register, wrapped to an address in memory like the PC
+    pub fn set_i(&mut self, value: u16) {
+        self.i = value % RAM_SIZE as u16;
+    }
+
+    /// Change the address of the next instruction
+    pub fn set_pc(&mut self, address: u16) {
+        self.pc = address % RAM_SIZE as u16;
+    }
+
+    /// Change the value of the delay timer
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    /// Change the value of the sound timer
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Returns the return addresses on the stack, the most recent call last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    /// Returns the instruction at `address`, without running it
+    pub fn instruction_at(&self, address: u16) -> u16 {
+        let address = address as usize % RAM_SIZE;
+        (self.ram[address] as u16) << 8 | self.ram[(address + 1) % RAM_SIZE] as u16
+    }
+
     /// Returns the current state of the screen
     pub fn screen(&self) -> &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT] {
         &self.screen
@@ -128,11 +430,8 @@ impl Emulator {
     /// Execute the instruction and do what it tells you
     pub fn execute(&mut self) {
         let decoded_operation: Opcode = self.decode();
-        debug!("Opcode decoded as {:?}", decoded_operation);
-        debug!("Current state of RAM {:?}", self.ram);
-        debug!("Current state of Registers {:?}", self.variable_registers);
-
-        self.handle_timers();
+        self.cycles += 1;
+        self.accesses.clear();
 
         match decoded_operation.category {
             0x0 => match decoded_operation.nnn {
@@ -209,8 +508,6 @@ impl Emulator {
     fn fetch(&mut self) -> u16 {
         let mut instruction: u16 = (self.fetch_next_byte() as u16) << 8;
         instruction |= self.fetch_next_byte() as u16;
-
-        debug!("Fetched instruction: {:#X}", instruction);
         instruction
     }
 
@@ -224,7 +521,9 @@ impl Emulator {
 
     /// Clear the display, turning all pixels off to 0
     fn clear_screen(&mut self) {
-        debug!("Clearing screen");
+        for y in 0..SCREEN_HEIGHT {
+            self.save_screen_row(y);
+        }
         for i in 0..SCREEN_HEIGHT {
             for j in 0..SCREEN_WIDTH {
                 self.screen[i][j] = false;
@@ -235,14 +534,12 @@ impl Emulator {
 
     /// Set the PC counter to `memory_location` which is 12-bit, despite using u16 to represent it
     fn jump(&mut self, memory_location: u16) {
-        debug!("Jumping to {:#X}", memory_location);
         self.pc = memory_location
     }
 
     /// Set the PC counter to `memory_location` which is 12-bit, despite using u16 to represent it
     /// and push the current PC to the stack, so the subroutine can return later
     fn subroutine_call(&mut self, memory_location: u16) {
-        debug!("Subroutine call to {:#X}", memory_location);
         self.stack.push(self.pc);
         self.pc = memory_location;
     }
@@ -250,32 +547,31 @@ impl Emulator {
     /// Pop the last instruction from the stack and set the PC to it.
     /// used to return from a subroutine
     fn subroutine_exit(&mut self) {
-        debug!("Exiting subroutine");
         if let Some(exit_location) = self.stack.pop() {
             self.pc = exit_location
         } else {
-            error!("No memory location found in stack, to return to");
-            panic!("Attempted to exit subroutine with nowhere to return to")
+            self.pc = self.pc.wrapping_sub(2) % RAM_SIZE as u16;
+            self.fault = Some(format!(
+                "00EE at {:03X} returned with nothing on the stack",
+                self.pc
+            ));
         }
     }
 
     /// Set register `reg` to `value`
     fn set_register_to_val(&mut self, reg: u8, value: u8) {
-        debug!("Setting register {} to value {}", reg, value);
         self.variable_registers[reg as usize] = value;
     }
 
     /// Add `value` to register `reg`
     /// Do not set carry flag on overflow
     fn add_val_to_register(&mut self, reg: u8, value: u8) {
-        debug!("Adding value {} to register {}", value, reg);
         let i: usize = reg as usize;
         self.variable_registers[i] = self.variable_registers[i].wrapping_add(value)
     }
 
     /// Set index register `i` to 12-bit value (represented using u16)
     fn set_index_register(&mut self, value: u16) {
-        debug!("Setting index register to value {:#X}", value);
         self.i = value
     }
 
@@ -285,16 +581,13 @@ impl Emulator {
     /// drawn to (from left to right, from most to least significant bit). If any pixels on the
     /// screen were turned “off” by this, the VF flag register is set to 1. Otherwise, it’s set to 0.
     fn display(&mut self, x_reg: u8, y_reg: u8, sprite_height: u8) {
-        debug!(
-            "Drawing {} pixel tall sprite, using X=V[{}], Y=V[{}]",
-            sprite_height, y_reg, x_reg
-        );
         let x_coord: usize = self.variable_registers[x_reg as usize] as usize % SCREEN_WIDTH;
         let y_coord: usize = self.variable_registers[y_reg as usize] as usize % SCREEN_HEIGHT;
         self.variable_registers[15] = 0;
 
         for i in 0..sprite_height {
-            let sprite_row: u8 = self.ram[self.i as usize + i as usize];
+            self.save_screen_row(y_coord + i as usize);
+            let sprite_row: u8 = self.read_ram(self.i as usize + i as usize);
             for j in 0..8 {
                 let x = x_coord + j;
                 let y = y_coord + i as usize;
@@ -316,7 +609,6 @@ impl Emulator {
     /// Check if the value in `reg_num` is equal to `value` and increments PC by 2
     /// if that's the case
     fn skip_if_equal(&mut self, reg_num: u8, value: u8) {
-        debug!("Skipping if register {} equals {}", reg_num, value);
         if self.variable_registers[reg_num as usize] == value {
             self.pc += 2;
         }
@@ -325,7 +617,6 @@ impl Emulator {
     /// Check if the value in `reg_num` is not equal to `value` and increments PC by 2
     /// if that's the case
     fn skip_if_not_equal(&mut self, reg_num: u8, value: u8) {
-        debug!("Skipping if register {} does not equal {}", reg_num, value);
         if self.variable_registers[reg_num as usize] != value {
             self.pc += 2;
         }
@@ -334,7 +625,6 @@ impl Emulator {
     /// Check if the value in `x_reg` is equal to the value in `y_reg` and
     /// increments PC by 2 if that's the case
     fn skip_if_regs_equal(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Skipping if register {} equals register {}", x_reg, y_reg);
         if self.variable_registers[x_reg as usize] == self.variable_registers[y_reg as usize] {
             self.pc += 2;
         }
@@ -343,10 +633,6 @@ impl Emulator {
     /// Check if the value in `x_reg` is not equal to the value in `y_reg` and
     /// increments PC by 2 if that's the case
     fn skip_if_regs_not_equal(&mut self, x_reg: u8, y_reg: u8) {
-        debug!(
-            "Skipping if register {} does not equal register {}",
-            x_reg, y_reg
-        );
         if self.variable_registers[x_reg as usize] != self.variable_registers[y_reg as usize] {
             self.pc += 2;
         }
@@ -354,38 +640,30 @@ impl Emulator {
 
     /// Sets register `x_reg` to the value of register `y_reg`
     fn set_register_to_register(&mut self, x_reg: u8, y_reg: u8) {
-        debug!(
-            "Setting if register {} to value of register {}",
-            x_reg, y_reg
-        );
         self.variable_registers[x_reg as usize] = self.variable_registers[y_reg as usize];
     }
 
     /// Sets register `x_reg` to the result of a bitwise OR between the values in
     /// registers `x_reg` and `y_reg`
     fn bitwise_or(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Bitwise OR of register {} and {}", x_reg, y_reg);
         self.variable_registers[x_reg as usize] |= self.variable_registers[y_reg as usize];
     }
 
     /// Sets register `x_reg` to the result of a bitwise AND between the values in
     /// registers `x_reg` and `y_reg`
     fn bitwise_and(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Bitwise AND of register {} and {}", x_reg, y_reg);
         self.variable_registers[x_reg as usize] &= self.variable_registers[y_reg as usize];
     }
 
     /// Sets register `x_reg` to the result of a bitwise XOR between the values in
     /// registers `x_reg` and `y_reg`
     fn bitwise_xor(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Bitwise XOR of register {} and {}", x_reg, y_reg);
         self.variable_registers[x_reg as usize] ^= self.variable_registers[y_reg as usize];
     }
 
     /// Sets register `x_reg` to the result of adding the value of `y_reg` to it
     /// If overflow occurs, sets the flag register to 1, otherwise sets it to 0
     fn add_register_to_register(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Adding value of register {} to register {}", y_reg, x_reg);
         let (result, overflow) = self.variable_registers[x_reg as usize]
             .overflowing_add(self.variable_registers[y_reg as usize]);
         self.variable_registers[x_reg as usize] = result;
@@ -395,7 +673,6 @@ impl Emulator {
     /// Sets register `x_reg` to the result of subtracting the value of `y_reg` from it
     /// If overflow occurs, sets the flag register to 0, otherwise sets it to 1
     fn subtract_yregister_from_xregister(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Subtracting value of register {} from {}", y_reg, x_reg);
         let (result, overflow) = self.variable_registers[x_reg as usize]
             .overflowing_sub(self.variable_registers[y_reg as usize]);
         self.variable_registers[x_reg as usize] = result;
@@ -405,7 +682,6 @@ impl Emulator {
     /// Sets register `x_reg` to the result of subtracting it from the value of `y_reg`
     /// If overflow occurs, sets the flag register to 0, otherwise sets it to 1
     fn subtract_xregister_from_yregister(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Subtracting value of register {} from {}", x_reg, y_reg);
         let (result, overflow) = self.variable_registers[y_reg as usize]
             .overflowing_sub(self.variable_registers[x_reg as usize]);
         self.variable_registers[x_reg as usize] = result;
@@ -417,8 +693,7 @@ impl Emulator {
     /// making it so it's the shifted value of `y_reg` that exists in `x_reg`.
     /// Sets the flag register to the value of the bit that was shifted out.
     fn shift_to_right(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Shifting value of register to the right");
-        if self.use_y_on_shift {
+        if self.quirks.use_y_on_shift {
             self.variable_registers[x_reg as usize] = self.variable_registers[y_reg as usize]
         }
         self.variable_registers[15] = self.variable_registers[x_reg as usize] & 1;
@@ -430,8 +705,7 @@ impl Emulator {
     /// making it so it's the shifted value of `y_reg` that exists in `x_reg`.
     /// Sets the flag register to the value of the bit that was shifted out.
     fn shift_to_left(&mut self, x_reg: u8, y_reg: u8) {
-        debug!("Shifting value of register to the left");
-        if self.use_y_on_shift {
+        if self.quirks.use_y_on_shift {
             self.variable_registers[x_reg as usize] = self.variable_registers[y_reg as usize]
         }
         self.variable_registers[15] = (self.variable_registers[x_reg as usize] >> 7) & 1;
@@ -441,8 +715,7 @@ impl Emulator {
     /// Jump to memory location of the Register 0 plus the `offset`.
     /// If `use_x_on_jump` is true, then uses Register X instead of Register 0.
     fn jump_with_offset(&mut self, x_reg: u8, offset: u16) {
-        debug!("Jumping with offset {:#X}", offset);
-        self.pc = if self.use_x_on_jump {
+        self.pc = if self.quirks.use_x_on_jump {
             self.variable_registers[x_reg as usize] as u16 + offset
         } else {
             self.variable_registers[0] as u16 + offset
@@ -452,17 +725,12 @@ impl Emulator {
     /// Generate a random u8 number, binary AND it with `value` and put the result into
     /// register `x_reg`
     fn random(&mut self, x_reg: u8, value: u8) {
-        debug!("Generating random number");
-        self.variable_registers[x_reg as usize] = rand::random_range(0..=255) & value;
+        self.variable_registers[x_reg as usize] = self.rng.random_range(0..=255) & value;
     }
 
     /// Skip one instruction (increment PC by 2) if the key corresponding to the value in
     /// register `x_reg` is pressed
     fn skip_if_key_pressed(&mut self, reg: u8) {
-        debug!(
-            "Skipping next instruction if key in register {} is pressed",
-            reg
-        );
         if self.keypad.get_keys()[self.variable_registers[reg as usize] as usize] {
             self.pc += 2;
         }
@@ -471,10 +739,6 @@ impl Emulator {
     /// Skip one instruction (increment PC by 2) if the key corresponding to the value in
     /// register `x_reg` is not pressed
     fn skip_if_key_not_pressed(&mut self, reg: u8) {
-        debug!(
-            "Skipping next instruction if key in register {} is not pressed",
-            reg
-        );
         if !self.keypad.get_keys()[self.variable_registers[reg as usize] as usize] {
             self.pc += 2;
         }
@@ -482,22 +746,16 @@ impl Emulator {
 
     /// Sets value of register `reg` to delay timer value
     fn set_register_to_delay_timer(&mut self, reg: u8) {
-        debug!(
-            "Setting register {} to delay timer value {}",
-            reg, self.delay_timer
-        );
         self.variable_registers[reg as usize] = self.delay_timer;
     }
 
     /// Sets value delay timer to value in register `reg`
     fn set_delay_timer_to_register_value(&mut self, reg: u8) {
-        debug!("Setting delay timer to value in register {}", reg);
         self.delay_timer = self.variable_registers[reg as usize];
     }
 
     /// Sets value sound timer to value in register `reg`
     fn set_sound_timer_to_register_value(&mut self, reg: u8) {
-        debug!("Setting sound timer to value in register {}", reg);
         self.sound_timer = self.variable_registers[reg as usize];
     }
 
@@ -505,7 +763,6 @@ impl Emulator {
     /// overflow flag if overflow occurs
     /// see note here: https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx1e-add-to-index
     fn add_register_to_index_register(&mut self, reg: u8) {
-        debug!("Adding value of register {} to index register", reg);
         let result: u16 = self.i + self.variable_registers[reg as usize] as u16;
         if result > 0xFFF {
             self.variable_registers[15] = 1;
@@ -518,10 +775,9 @@ impl Emulator {
     /// Stop executing instructions until a key is pressed
     /// when a key is pressed, put its value into the register `reg`
     fn block_and_wait_for_key(&mut self, reg: u8) {
-        info!("Waiting for a key press, to put into register {}", reg);
         for i in 0..NUM_KEYS {
             if self.keypad.get_keys()[i] {
-                self.variable_registers[i] = i as u8;
+                self.variable_registers[reg as usize] = i as u8;
                 return;
             }
         }
@@ -531,26 +787,18 @@ impl Emulator {
     /// Sets to index register to the location of the font of the hexidecimal
     /// number stored in register `reg`
     fn set_index_register_to_font_location(&mut self, reg: u8) {
-        debug!(
-            "Setting index register to font of number stored in register {}",
-            reg
-        );
-        self.i = self.variable_registers[reg as usize] as u16 * 5;
+        self.i = self.variable_registers[reg as usize] as u16 * FONT_HEIGHT as u16;
     }
 
     /// Takes the number in register `reg` as a decimal number,
     /// takes each of it's digits and stores them in memory
     /// at the memory address of the index register
     fn store_register_digits_in_memory(&mut self, reg: u8) {
-        debug!(
-            "Storing digits from value in register {} in memory at address {:#X}",
-            reg, self.i
-        );
         let index: usize = self.i as usize;
         let value = self.variable_registers[reg as usize];
-        self.ram[index] = value / 100;
-        self.ram[index + 1] = (value % 100) / 10;
-        self.ram[index + 2] = value % 10;
+        self.write_ram(index, value / 100);
+        self.write_ram(index + 1, (value % 100) / 10);
+        self.write_ram(index + 2, value % 10);
     }
 
     /// Store the value of each register from 0-`reg` in successive memory addresses
@@ -558,17 +806,13 @@ impl Emulator {
     /// If `modify_i_on_load_and_store` is true, the the index register gets modified
     /// to `i + reg + 1`
     fn store_to_memory_from_register(&mut self, reg: u8) {
-        debug!(
-            "Storing to addresses {:#X} to {:#X}. From registers {} to {}",
-            self.i,
-            self.i + reg as u16,
-            0,
-            reg
-        );
         for i in 0..=reg {
-            self.ram[self.i as usize + i as usize] = self.variable_registers[i as usize];
+            self.write_ram(
+                self.i as usize + i as usize,
+                self.variable_registers[i as usize],
+            );
         }
-        if self.modify_i_on_load_and_store {
+        if self.quirks.modify_i_on_load_and_store {
             self.i = self.i + reg as u16 + 1;
         }
     }
@@ -578,22 +822,171 @@ impl Emulator {
     /// If `modify_i_on_load_and_store` is true, the the index register gets modified
     /// to `i + reg + 1`
     fn load_from_memory_to_register(&mut self, reg: u8) {
-        debug!(
-            "Loading from addresses {:#X} to {:#X}. To registers {} to {}",
-            self.i,
-            self.i + reg as u16,
-            0,
-            reg
-        );
         for i in 0..=reg {
-            self.variable_registers[i as usize] = self.ram[self.i as usize + i as usize];
+            self.variable_registers[i as usize] = self.read_ram(self.i as usize + i as usize);
         }
-        if self.modify_i_on_load_and_store {
+        if self.quirks.modify_i_on_load_and_store {
             self.i = self.i + reg as u16 + 1;
         }
     }
+
+    /// Read the byte at `address` as data, wrapping around past the end of memory
+    fn read_ram(&mut self, address: usize) -> u8 {
+        let address = address % RAM_SIZE;
+        self.accesses.push(MemoryAccess::Read(address as u16));
+        self.ram[address]
+    }
+
+    /// Write `value` to memory at `address`, remembering when it was written, wrapping
+    /// around past the end of memory
+    fn write_ram(&mut self, address: usize, value: u8) {
+        let address = address % RAM_SIZE;
+        if let Some(undo) = self.undo.as_mut() {
+            undo.ram
+                .push((address, self.ram[address], self.write_cycles[address]));
+        }
+        self.ram[address] = value;
+        self.write_cycles[address] = self.cycles;
+        self.accesses.push(MemoryAccess::Write(address as u16));
+    }
+}
+
+/// Random number generator from `seed`, or from a random seed if None
+fn new_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
 }
 
 fn warn_unknown_operation(operation: Opcode) {
     warn!("Unknown Operation {:?}", operation);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything an instruction can change
+    #[derive(Debug, PartialEq)]
+    struct Snapshot {
+        pc: u16,
+        i: u16,
+        registers: [u8; NUM_VARIABLE_REGISTERS],
+        stack: Vec<u16>,
+        delay_timer: u8,
+        sound_timer: u8,
+        ram: [u8; RAM_SIZE],
+        write_cycles: Vec<u64>,
+        screen: [[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        cycles: u64,
+    }
+
+    fn snapshot(emu: &Emulator) -> Snapshot {
+        Snapshot {
+            pc: emu.pc,
+            i: emu.i,
+            registers: emu.variable_registers,
+            stack: emu.stack.clone(),
+            delay_timer: emu.delay_timer,
+            sound_timer: emu.sound_timer,
+            ram: emu.ram,
+            write_cycles: emu.write_cycles.clone(),
+            screen: emu.screen,
+            cycles: emu.cycles,
+        }
+    }
+
+    #[test]
+    fn step_back_undoes_each_instruction() {
+        #[rustfmt::skip]
+        let program = vec![
+            0x60, 0x85, // 200: LD V0, 0x85
+            0xF0, 0x15, // 202: LD DT, V0
+            0xA3, 0x00, // 204: LD I, 0x300
+            0x22, 0x10, // 206: CALL 210
+            0x12, 0x08, // 208: JP 208
+            0x00, 0x00,
+            0x00, 0x00,
+            0x00, 0x00,
+            0xF0, 0x33, // 210: LD B, V0
+            0xF1, 0x55, // 212: LD [I], V1
+            0xD0, 0x13, // 214: DRW V0, V1, 3
+            0xC2, 0xFF, // 216: RND V2, 0xFF
+            0xC3, 0xFF, // 218: RND V3, 0xFF
+            0x00, 0xEE, // 21A: RET
+        ];
+        let mut emu = Emulator::new(Quirks::default(), Some(8));
+        emu.load_rom(program).unwrap();
+        emu.set_history_limit(100);
+
+        let mut snapshots = vec![snapshot(&emu)];
+        for _ in 0..12 {
+            emu.step(1);
+            snapshots.push(snapshot(&emu));
+        }
+        assert_eq!(emu.stack(), &[] as &[u16]);
+        assert_eq!(emu.pc(), 0x208);
+        assert_ne!(emu.ram()[0x300..0x303], [0, 0, 0]);
+        assert!(emu.screen().iter().flatten().any(|&pixel| pixel));
+
+        for expected in snapshots.iter().rev().skip(1) {
+            assert!(emu.step_back());
+            assert_eq!(&snapshot(&emu), expected);
+        }
+        assert!(!emu.step_back());
+
+        // stepping forward again draws the same random numbers
+        for expected in snapshots.iter().skip(1) {
+            emu.step(1);
+            assert_eq!(&snapshot(&emu), expected);
+        }
+    }
+
+    #[test]
+    fn reset_starts_counting_and_history_again() {
+        let mut emu = Emulator::new(Quirks::default(), Some(1));
+        // 200: LD I, 0x300, 202: LD [I], V0, 204: JP 204
+        emu.load_rom(vec![0xA3, 0x00, 0xF0, 0x55, 0x12, 0x04])
+            .unwrap();
+        emu.set_history_limit(10);
+        emu.step(1);
+        emu.step(1);
+        assert_eq!(emu.cycles(), 2);
+        assert!(!emu.last_accesses().is_empty());
+
+        emu.reset();
+        assert_eq!(emu.cycles(), 0);
+        assert!(emu.last_accesses().is_empty());
+        assert!(emu.write_cycles.iter().all(|&cycle| cycle == 0));
+        assert!(!emu.step_back());
+        assert_eq!(emu.pc(), START_ADDR);
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut emu = Emulator::new(Quirks::default(), Some(1));
+        emu.load_rom(vec![0x70, 0x01, 0x12, 0x00]).unwrap();
+        emu.set_history_limit(3);
+        for _ in 0..10 {
+            emu.step(1);
+        }
+        let registers = emu.registers()[0];
+        assert!(emu.step_back() && emu.step_back() && emu.step_back());
+        assert!(!emu.step_back());
+        assert_eq!(emu.registers()[0], registers - 1);
+    }
+
+    #[test]
+    fn wait_for_key_stores_it_in_vx() {
+        let mut emu = Emulator::new(Quirks::default(), Some(1));
+        emu.load_rom(vec![0xF3, 0x0A, 0x12, 0x02]).unwrap();
+        emu.step(1);
+        assert_eq!(emu.pc(), 0x200, "keeps waiting until a key is pressed");
+        emu.keypad.set_key(7, true);
+        emu.step(1);
+        assert_eq!(emu.pc(), 0x202);
+        assert_eq!(emu.registers()[3], 7);
+        assert_eq!(emu.registers()[7], 0);
+    }
+}
@@ -0,0 +1,402 @@
+/// Expressions for breakpoint conditions and watchpoints, e.g. `V3 == 0x10 && I > 0x300`.
+///
+/// Values are the registers V0 to VF, I, PC, SP (the number of return addresses on the
+/// stack), DT and ST (the timers), HITS (the number of times the breakpoint has been
+/// reached, including this one), bytes of memory like `[0x300]` or `[I + 1]`, and numbers
+/// in decimal or hex with `0x`. The operators are those of Rust, from loosest to tightest:
+/// `||`, `&&`, `== != < <= > >=`, `|`, `&`, `+ -`, and `!` and `-` in front of a value.
+/// Comparisons are 1 if true and 0 if false, and anything but 0 is true
+use std::fmt;
+
+use crate::emulator::{Emulator, RAM_SIZE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Register(usize),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+    Hits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BitOr,
+    BitAnd,
+    Add,
+    Subtract,
+}
+
+/// Binary operators by precedence, loosest first
+const PRECEDENCE: [&[(&str, Operator)]; 6] = [
+    &[("||", Operator::Or)],
+    &[("&&", Operator::And)],
+    &[
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessOrEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+    ],
+    &[("|", Operator::BitOr)],
+    &[("&", Operator::BitAnd)],
+    &[("+", Operator::Add), ("-", Operator::Subtract)],
+];
+
+/// Symbols, the longest first so `<=` isn't read as `<`
+const SYMBOLS: [&str; 17] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "&", "+", "-", "!", "(", ")", "[", "]",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(i64),
+    Value(Value),
+    Memory(Box<Node>),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    Binary(Box<Node>, Operator, Box<Node>),
+}
+
+/// A parsed expression, shown as it was written
+#[derive(Debug, Clone)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+        let root = parser.binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {} in {}", describe(token), source));
+        }
+        Ok(Self {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    /// The value of the expression in the current state of `emu`, with `hits` as HITS
+    pub fn evaluate(&self, emu: &Emulator, hits: u64) -> i64 {
+        evaluate(&self.root, emu, hits)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            tokens.push(if c.is_ascii_digit() {
+                Token::Number(parse_number(word)?)
+            } else {
+                Token::Name(word.to_ascii_uppercase())
+            });
+            rest = &rest[end..];
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(format!("Unexpected {} in {}", c, source));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Result<i64, String> {
+    let parsed = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+    parsed.map_err(|_| format!("{} is not a number", word))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(number) => number.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Symbol(symbol) => symbol.to_string(),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| "Expression ends too soon".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(found) if found == symbol => Ok(()),
+            token => Err(format!(
+                "Expected {} but found {}",
+                symbol,
+                describe(&token)
+            )),
+        }
+    }
+
+    /// Operators of `level` of the precedence table and tighter, left to right
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Symbol(symbol)) = self.peek() {
+            let Some((_, operator)) = PRECEDENCE[level].iter().find(|(s, _)| s == symbol) else {
+                break;
+            };
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Node::Binary(Box::new(left), *operator, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            Token::Number(number) => Ok(Node::Number(number)),
+            Token::Name(name) => parse_value(&name).map(Node::Value),
+            Token::Symbol("!") => Ok(Node::Not(Box::new(self.unary()?))),
+            Token::Symbol("-") => Ok(Node::Negate(Box::new(self.unary()?))),
+            Token::Symbol("(") => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Symbol("[") => {
+                let address = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Node::Memory(Box::new(address)))
+            }
+            token => Err(format!("Unexpected {}", describe(&token))),
+        }
+    }
+}
+
+fn parse_value(name: &str) -> Result<Value, String> {
+    let value = match name {
+        "I" => Value::I,
+        "PC" => Value::Pc,
+        "SP" => Value::Sp,
+        "DT" => Value::DelayTimer,
+        "ST" => Value::SoundTimer,
+        "HITS" => Value::Hits,
+        _ => match name.strip_prefix('V') {
+            Some(reg) if reg.len() == 1 => {
+                Value::Register(usize::from_str_radix(reg, 16).map_err(|_| unknown(name))?)
+            }
+            _ => return Err(unknown(name)),
+        },
+    };
+    Ok(value)
+}
+
+fn unknown(name: &str) -> String {
+    format!("{} is not one of V0-VF, I, PC, SP, DT, ST or HITS", name)
+}
+
+fn evaluate(node: &Node, emu: &Emulator, hits: u64) -> i64 {
+    match node {
+        Node::Number(number) => *number,
+        Node::Value(value) => match value {
+            Value::Register(reg) => emu.registers()[*reg] as i64,
+            Value::I => emu.i() as i64,
+            Value::Pc => emu.pc() as i64,
+            Value::Sp => emu.stack().len() as i64,
+            Value::DelayTimer => emu.delay_timer() as i64,
+            Value::SoundTimer => *emu.sound_timer() as i64,
+            Value::Hits => hits as i64,
+        },
+        Node::Memory(address) => {
+            let address = evaluate(address, emu, hits).rem_euclid(RAM_SIZE as i64);
+            emu.ram()[address as usize] as i64
+        }
+        Node::Not(inner) => (evaluate(inner, emu, hits) == 0) as i64,
+        Node::Negate(inner) => evaluate(inner, emu, hits).wrapping_neg(),
+        Node::Binary(left, operator, right) => {
+            let left = evaluate(left, emu, hits);
+            // the right side of && and || is only evaluated if it matters
+            match operator {
+                Operator::Or => return (left != 0 || evaluate(right, emu, hits) != 0) as i64,
+                Operator::And => return (left != 0 && evaluate(right, emu, hits) != 0) as i64,
+                _ => {}
+            }
+            let right = evaluate(right, emu, hits);
+            match operator {
+                Operator::Equal => (left == right) as i64,
+                Operator::NotEqual => (left != right) as i64,
+                Operator::Less => (left < right) as i64,
+                Operator::LessOrEqual => (left <= right) as i64,
+                Operator::Greater => (left > right) as i64,
+                Operator::GreaterOrEqual => (left >= right) as i64,
+                Operator::BitOr => left | right,
+                Operator::BitAnd => left & right,
+                Operator::Add => left.wrapping_add(right),
+                Operator::Subtract => left.wrapping_sub(right),
+                Operator::Or | Operator::And => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Quirks;
+
+    fn emulator() -> Emulator {
+        let mut emu = Emulator::new(Quirks::default(), Some(1));
+        emu.set_register(3, 0x10);
+        emu.set_register(0xF, 1);
+        emu.set_i(0x300);
+        emu.poke(0x300, 0xAA);
+        emu.poke(0x301, 0xBB);
+        emu
+    }
+
+    fn eval(source: &str) -> i64 {
+        Expression::parse(source).unwrap().evaluate(&emulator(), 3)
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(eval("V3"), 0x10);
+        assert_eq!(eval("vf"), 1);
+        assert_eq!(eval("I"), 0x300);
+        assert_eq!(eval("PC"), 0x200);
+        assert_eq!(eval("SP"), 0);
+        assert_eq!(eval("HITS"), 3);
+        assert_eq!(eval("0x1F"), 31);
+        assert_eq!(eval("[0x300]"), 0xAA);
+        assert_eq!(eval("[I + 1]"), 0xBB);
+        assert_eq!(eval("[I + 0x1000]"), 0xAA); // wraps around memory
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 == 3"), 1);
+        assert_eq!(eval("6 & 3 == 2"), 1); // & binds tighter than ==
+        assert_eq!(eval("4 | 1 & 3"), 5); // & binds tighter than |
+        assert_eq!(eval("1 || 0 && 0"), 1); // && binds tighter than ||
+        assert_eq!(eval("(1 || 0) && 0"), 0);
+        assert_eq!(eval("10 - 3 - 2"), 5); // left to right
+        assert_eq!(eval("-V3 + 1"), -15);
+        assert_eq!(eval("!V3 == 0"), 1);
+        assert_eq!(eval("V3 == 0x10 && I > 0x2FF"), 1);
+        assert_eq!(eval("V3 >= 0x11 || I <= 0x2FF"), 0);
+    }
+
+    #[test]
+    fn and_or_short_circuit_to_0_or_1() {
+        assert_eq!(eval("0 && V3"), 0);
+        assert_eq!(eval("V3 && 0"), 0);
+        assert_eq!(eval("V3 && I"), 1);
+        assert_eq!(eval("V3 || 0"), 1);
+        assert_eq!(eval("0 || 0"), 0);
+        assert_eq!(eval("0 || [I]"), 1);
+    }
+
+    #[test]
+    fn shown_as_written() {
+        let expression = Expression::parse("  V3 == 0x10 ").unwrap();
+        assert_eq!(expression.to_string(), "V3 == 0x10");
+    }
+
+    #[test]
+    fn registers_are_v0_to_vf() {
+        assert!(Expression::parse("V0").is_ok());
+        assert!(Expression::parse("VF").is_ok());
+        for name in ["VG", "V10", "V", "X"] {
+            assert_eq!(
+                Expression::parse(name).unwrap_err(),
+                format!("{} is not one of V0-VF, I, PC, SP, DT, ST or HITS", name)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Expression::parse("").unwrap_err(),
+            "Expression ends too soon"
+        );
+        assert_eq!(
+            Expression::parse("V3 ==").unwrap_err(),
+            "Expression ends too soon"
+        );
+        assert_eq!(
+            Expression::parse("(V3").unwrap_err(),
+            "Expression ends too soon"
+        );
+        assert_eq!(
+            Expression::parse("[I + 1").unwrap_err(),
+            "Expression ends too soon"
+        );
+        assert_eq!(
+            Expression::parse("(V3]").unwrap_err(),
+            "Expected ) but found ]"
+        );
+        assert_eq!(
+            Expression::parse("V3 V4").unwrap_err(),
+            "Unexpected V4 in V3 V4"
+        );
+        assert_eq!(
+            Expression::parse("V3 $ 1").unwrap_err(),
+            "Unexpected $ in V3 $ 1"
+        );
+        assert_eq!(
+            Expression::parse("0xZZ").unwrap_err(),
+            "0xZZ is not a number"
+        );
+        assert_eq!(Expression::parse("== 1").unwrap_err(), "Unexpected ==");
+    }
+}
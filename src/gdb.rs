@@ -0,0 +1,630 @@
+/// Stub for the GDB remote serial protocol, so debugger frontends can control the emulator
+/// over TCP: https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+///
+/// Registers are numbered V0 to VF (0-15, one byte each), I (16, two bytes), PC (17, two
+/// bytes), SP (18, one byte, the number of return addresses on the stack), DT and ST (19
+/// and 20, one byte each). Multi byte registers are sent little endian, and the layout is
+/// described to the debugger in `target.xml`. Memory addresses are the CHIP8's own, 0 to 0xFFF
+use log::{info, warn};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::debugger::{Debugger, StopReason};
+use crate::emulator::{Emulator, NUM_VARIABLE_REGISTERS, RAM_SIZE};
+
+const INTERRUPT: u8 = 0x03;
+const I_REGISTER: usize = NUM_VARIABLE_REGISTERS;
+const PC_REGISTER: usize = NUM_VARIABLE_REGISTERS + 1;
+const SP_REGISTER: usize = NUM_VARIABLE_REGISTERS + 2;
+const DT_REGISTER: usize = NUM_VARIABLE_REGISTERS + 3;
+const ST_REGISTER: usize = NUM_VARIABLE_REGISTERS + 4;
+
+/// Replies are written with the socket blocking, giving up on a debugger that stops
+/// reading for this long
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Description of the registers, so debuggers don't assume their host's layout
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// Listens for one debugger at a time on a localhost port, polled from the main loop
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    input: Vec<u8>,
+    waiting_for_stop: bool, // the debugger is waiting to hear the emulator has stopped
+}
+
+impl GdbStub {
+    pub fn listen(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .map_err(|e| format!("Could not listen for GDB on port {}: {}", port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        info!("Waiting for GDB to connect to localhost:{}", port);
+        Ok(Self {
+            listener,
+            client: None,
+            input: Vec::new(),
+            waiting_for_stop: false,
+        })
+    }
+
+    /// Accept a connection, answer any packets that have arrived and report when the
+    /// emulator stops. Never blocks. A connection that fails is dropped and the emulator
+    /// is left to run freely
+    pub fn poll(&mut self, emu: &mut Emulator, debugger: &mut Debugger) {
+        if let Err(e) = self.try_poll(emu, debugger) {
+            warn!("GDB connection lost: {}", e);
+            self.disconnect(debugger);
+        }
+    }
+
+    fn try_poll(&mut self, emu: &mut Emulator, debugger: &mut Debugger) -> io::Result<()> {
+        if self.client.is_none() && !self.accept(debugger)? {
+            return Ok(());
+        }
+        if !self.read_input()? {
+            info!("GDB disconnected");
+            self.disconnect(debugger);
+            return Ok(());
+        }
+
+        while let Some(packet) = self.next_packet()? {
+            match packet {
+                Packet::Interrupt => {
+                    debugger.halt();
+                    self.waiting_for_stop = true;
+                }
+                Packet::Command(command) => {
+                    if let Some(reply) = self.handle_command(&command, emu, debugger) {
+                        self.send_packet(&reply)?;
+                    }
+                    if matches!(command.as_bytes().first(), Some(b'D' | b'k')) {
+                        info!("GDB detached");
+                        self.disconnect(debugger);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        if self.waiting_for_stop && debugger.is_halted() {
+            self.waiting_for_stop = false;
+            self.send_packet(&stop_reply(debugger))?;
+        }
+        Ok(())
+    }
+
+    /// Take a waiting connection, halting the emulator for it. Returns false if there isn't one
+    fn accept(&mut self, debugger: &mut Debugger) -> io::Result<bool> {
+        let (stream, address) = match self.listener.accept() {
+            Ok(connection) => connection,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        info!("GDB connected from {}", address);
+        self.client = Some(stream);
+        self.input.clear();
+        self.waiting_for_stop = false;
+        debugger.halt();
+        Ok(true)
+    }
+
+    /// Read everything that has arrived. Returns false if the connection was closed
+    fn read_input(&mut self) -> io::Result<bool> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(false);
+        };
+        let mut buffer = [0; 1024];
+        loop {
+            match client.read(&mut buffer) {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.input.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(true),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Take the next complete packet from the input, acknowledging it
+    fn next_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            match self.input.first() {
+                None => return Ok(None),
+                Some(&INTERRUPT) => {
+                    self.input.remove(0);
+                    return Ok(Some(Packet::Interrupt));
+                }
+                Some(b'$') => break,
+                // acknowledgements of our packets, and anything else between packets
+                Some(_) => {
+                    self.input.remove(0);
+                }
+            }
+        }
+
+        let Some(end) = self.input.iter().position(|&b| b == b'#') else {
+            return Ok(None);
+        };
+        if self.input.len() < end + 3 {
+            return Ok(None);
+        }
+        let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+        let data = &packet[1..end];
+        let checksum = std::str::from_utf8(&packet[end + 1..])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if checksum != Some(checksum_of(data)) {
+            self.write(b"-")?;
+            return self.next_packet();
+        }
+        self.write(b"+")?;
+        Ok(Some(Packet::Command(
+            String::from_utf8_lossy(data).into_owned(),
+        )))
+    }
+
+    /// Reply to a command, None if the reply comes later, like the stop after a continue
+    fn handle_command(
+        &mut self,
+        command: &str,
+        emu: &mut Emulator,
+        debugger: &mut Debugger,
+    ) -> Option<String> {
+        let reply = match command.as_bytes().first()? {
+            b'?' => stop_reply(debugger),
+            b'g' => (0..=ST_REGISTER)
+                .map(|reg| read_register(emu, reg))
+                .collect(),
+            b'G' => write_registers(emu, &command[1..]),
+            b'p' => usize::from_str_radix(&command[1..], 16)
+                .ok()
+                .filter(|&reg| reg <= ST_REGISTER)
+                .map(|reg| read_register(emu, reg))
+                .unwrap_or_else(|| "E01".to_string()),
+            b'P' => write_register(emu, &command[1..]),
+            b'm' => read_memory(emu, &command[1..]),
+            b'M' => write_memory(emu, &command[1..]),
+            b'c' | b's' => {
+                if let Ok(address) = u16::from_str_radix(&command[1..], 16) {
+                    emu.set_pc(address);
+                }
+                if command.starts_with('c') {
+                    debugger.resume();
+                } else {
+                    debugger.step();
+                }
+                self.waiting_for_stop = true;
+                return None;
+            }
+            b'b' => {
+                match command {
+                    "bs" => debugger.step_back(),
+                    "bc" => debugger.run_back(),
+                    _ => return Some(String::new()),
+                }
+                self.waiting_for_stop = true;
+                return None;
+            }
+            b'Z' | b'z' => match parse_breakpoint(&command[1..]) {
+                Some(address) if command.starts_with('Z') => {
+                    debugger.add_breakpoint(address, None);
+                    "OK".to_string()
+                }
+                Some(address) => {
+                    debugger.remove_breakpoint(address);
+                    "OK".to_string()
+                }
+                // only software breakpoints are supported
+                None => String::new(),
+            },
+            b'H' | b'D' => "OK".to_string(),
+            b'k' => return None,
+            b'q' => match command.split(':').next()? {
+                "qSupported" => {
+                    "PacketSize=1000;qXfer:features:read+;ReverseStep+;ReverseContinue+".to_string()
+                }
+                "qXfer" => read_features(command),
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                query => match query.strip_prefix("qRcmd,") {
+                    Some(hex) => monitor_command(debugger, emu, hex),
+                    None => String::new(),
+                },
+            },
+            // an empty reply tells the debugger the command isn't supported
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.write(packet.as_bytes())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+        // block until it's all sent, or the write timeout runs out, rather than spinning
+        client.set_nonblocking(false)?;
+        let written = client.write_all(bytes);
+        client.set_nonblocking(true)?;
+        written
+    }
+
+    /// Drop the connection, letting the emulator carry on without breakpoints
+    fn disconnect(&mut self, debugger: &mut Debugger) {
+        self.client = None;
+        self.input.clear();
+        self.waiting_for_stop = false;
+        debugger.detach();
+    }
+}
+
+enum Packet {
+    Interrupt,       // Ctrl-C, sent outside of a packet
+    Command(String), // the data of a `$data#checksum` packet
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// Tell the debugger why the emulator stopped, as the signal a process would have got
+fn stop_reply(debugger: &Debugger) -> String {
+    match debugger.stop_reason() {
+        StopReason::Requested => "S02".to_string(), // SIGINT
+        StopReason::Breakpoint | StopReason::Watchpoint | StopReason::Step => {
+            "S05".to_string() // SIGTRAP
+        }
+        StopReason::Fault => "S04".to_string(), // SIGILL
+        StopReason::HistoryStart => "T05replaylog:begin;".to_string(),
+    }
+}
+
+/// Run a `monitor` command, sent as hex, replying with its output as hex
+fn monitor_command(debugger: &mut Debugger, emu: &Emulator, hex: &str) -> String {
+    let Some(command) = decode_hex(hex).and_then(|bytes| String::from_utf8(bytes).ok()) else {
+        return "E01".to_string();
+    };
+    let output = debugger.command(&command, emu).unwrap_or_else(|e| e);
+    format!("{}\n", output)
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Reply to `qXfer:features:read:target.xml:offset,length` with that part of the target
+/// description, starting with `l` if it's the last part and `m` if there's more
+fn read_features(command: &str) -> String {
+    let Some(args) = command.strip_prefix("qXfer:features:read:") else {
+        return String::new();
+    };
+    let Some(range) = args.strip_prefix("target.xml:") else {
+        return "E00".to_string(); // no such annex
+    };
+    let Some((offset, length)) = range.split_once(',').and_then(|(offset, length)| {
+        Some((
+            usize::from_str_radix(offset, 16).ok()?,
+            usize::from_str_radix(length, 16).ok()?,
+        ))
+    }) else {
+        return "E01".to_string();
+    };
+    let start = offset.min(TARGET_XML.len());
+    let end = start.saturating_add(length).min(TARGET_XML.len());
+    let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+    format!("{}{}", marker, &TARGET_XML[start..end])
+}
+
+fn read_register(emu: &Emulator, reg: usize) -> String {
+    match reg {
+        I_REGISTER => to_little_endian(emu.i()),
+        PC_REGISTER => to_little_endian(emu.pc()),
+        SP_REGISTER => format!("{:02x}", emu.stack().len()),
+        DT_REGISTER => format!("{:02x}", emu.delay_timer()),
+        ST_REGISTER => format!("{:02x}", emu.sound_timer()),
+        _ => format!("{:02x}", emu.registers()[reg]),
+    }
+}
+
+/// Set all registers from the hex of a `G` packet
+fn write_registers(emu: &mut Emulator, hex: &str) -> String {
+    let Some(bytes) = decode_hex(hex) else {
+        return "E01".to_string();
+    };
+    if bytes.len() < NUM_VARIABLE_REGISTERS + 4 {
+        return "E01".to_string();
+    }
+    let i = NUM_VARIABLE_REGISTERS;
+    let (Some(index), Some(pc)) = (
+        decode_address(&bytes[i..i + 2]),
+        decode_address(&bytes[i + 2..i + 4]),
+    ) else {
+        return "E01".to_string();
+    };
+    for (reg, &value) in bytes[..NUM_VARIABLE_REGISTERS].iter().enumerate() {
+        emu.set_register(reg, value);
+    }
+    emu.set_i(index);
+    emu.set_pc(pc);
+    // SP at i + 4 is read only, the timers after it are optional
+    if let [_, delay_timer, sound_timer, ..] = bytes[i + 4..] {
+        emu.set_delay_timer(delay_timer);
+        emu.set_sound_timer(sound_timer);
+    }
+    "OK".to_string()
+}
+
+/// Set one register from a `P` packet, `reg=value`. SP can't be changed
+fn write_register(emu: &mut Emulator, args: &str) -> String {
+    let parsed = args
+        .split_once('=')
+        .and_then(|(reg, value)| Some((usize::from_str_radix(reg, 16).ok()?, decode_hex(value)?)));
+    match parsed {
+        Some((reg, value)) if reg < NUM_VARIABLE_REGISTERS && value.len() == 1 => {
+            emu.set_register(reg, value[0])
+        }
+        Some((I_REGISTER, value)) => match decode_address(&value) {
+            Some(address) => emu.set_i(address),
+            None => return "E01".to_string(),
+        },
+        Some((PC_REGISTER, value)) => match decode_address(&value) {
+            Some(address) => emu.set_pc(address),
+            None => return "E01".to_string(),
+        },
+        Some((DT_REGISTER, value)) if value.len() == 1 => emu.set_delay_timer(value[0]),
+        Some((ST_REGISTER, value)) if value.len() == 1 => emu.set_sound_timer(value[0]),
+        _ => return "E01".to_string(),
+    }
+    "OK".to_string()
+}
+
+/// Reply to an `m` packet, `address,length`
+fn read_memory(emu: &Emulator, args: &str) -> String {
+    match parse_range(args) {
+        Some((address, length)) => emu.ram()[address..address + length]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+        None => "E14".to_string(), // EFAULT
+    }
+}
+
+/// Reply to an `M` packet, `address,length:bytes`
+fn write_memory(emu: &mut Emulator, args: &str) -> String {
+    let Some((range, hex)) = args.split_once(':') else {
+        return "E01".to_string();
+    };
+    let (Some((address, length)), Some(bytes)) = (parse_range(range), decode_hex(hex)) else {
+        return "E14".to_string();
+    };
+    if bytes.len() != length {
+        return "E01".to_string();
+    }
+    for (offset, &byte) in bytes.iter().enumerate() {
+        emu.poke((address + offset) as u16, byte);
+    }
+    "OK".to_string()
+}
+
+/// Parse `address,length` in hex, None if it's not all inside memory
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (address, length) = args.split_once(',')?;
+    let address = usize::from_str_radix(address, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+    (address.checked_add(length)? <= RAM_SIZE).then_some((address, length))
+}
+
+/// Address of a software breakpoint packet, `0,address,kind`
+fn parse_breakpoint(args: &str) -> Option<u16> {
+    let mut parts = args.split(',');
+    if parts.next()? != "0" {
+        return None;
+    }
+    u16::from_str_radix(parts.next()?, 16)
+        .ok()
+        .filter(|&address| (address as usize) < RAM_SIZE)
+}
+
+fn to_little_endian(value: u16) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A two byte little endian register value, if it's an address in memory
+fn decode_address(bytes: &[u8]) -> Option<u16> {
+    match bytes {
+        &[low, high] => Some(u16::from_le_bytes([low, high])).filter(|&a| (a as usize) < RAM_SIZE),
+        _ => None,
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Quirks;
+    use std::time::Instant;
+
+    // 200: V0 = 5, 202: V0 += 1, 204: jump to 202
+    const ROM: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+    /// The emulator's main loop with the stub, and the debugger's end of the connection
+    struct Session {
+        stub: GdbStub,
+        emu: Emulator,
+        debugger: Debugger,
+        client: TcpStream,
+        received: Vec<u8>,
+    }
+
+    impl Session {
+        fn connect() -> Self {
+            let stub = GdbStub::listen(0).unwrap();
+            let port = stub.listener.local_addr().unwrap().port();
+            let client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            client.set_nonblocking(true).unwrap();
+            let mut emu = Emulator::new(Quirks::default(), Some(1));
+            emu.load_rom(ROM.to_vec()).unwrap();
+            Self {
+                stub,
+                emu,
+                debugger: Debugger::new(true),
+                client,
+                received: Vec::new(),
+            }
+        }
+
+        /// Send a packet and run the emulator until the reply to it arrives
+        fn send(&mut self, data: &str) -> String {
+            let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+            self.client.write_all(packet.as_bytes()).unwrap();
+            self.reply(data)
+        }
+
+        fn reply(&mut self, sent: &str) -> String {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_secs(5) {
+                self.stub.poll(&mut self.emu, &mut self.debugger);
+                self.debugger.run_frame(&mut self.emu, 10);
+                let mut buffer = [0; 1024];
+                if let Ok(read) = self.client.read(&mut buffer) {
+                    self.received.extend_from_slice(&buffer[..read]);
+                }
+                // acknowledgements of our packets
+                while self.received.first() == Some(&b'+') {
+                    self.received.remove(0);
+                }
+                let Some(end) = self.received.iter().position(|&b| b == b'#') else {
+                    continue;
+                };
+                if self.received.len() < end + 3 {
+                    continue;
+                }
+                let packet: Vec<u8> = self.received.drain(..end + 3).collect();
+                assert_eq!(packet[0], b'$');
+                let data = &packet[1..end];
+                let checksum = std::str::from_utf8(&packet[end + 1..]).unwrap();
+                assert_eq!(u8::from_str_radix(checksum, 16), Ok(checksum_of(data)));
+                self.client.write_all(b"+").unwrap();
+                return String::from_utf8(data.to_vec()).unwrap();
+            }
+            panic!("No reply to {}", sent);
+        }
+    }
+
+    #[test]
+    fn registers_and_memory() {
+        let mut session = Session::connect();
+        assert_eq!(session.send("?"), "S02");
+        // V0-VF, then I, PC 0x200 little endian, SP, DT and ST
+        let registers = format!("{}0000000200{}", "00".repeat(16), "0000");
+        assert_eq!(session.send("g"), registers);
+        assert_eq!(session.send("m200,6"), "600570011202");
+        assert_eq!(session.send("m1000,1"), "E14");
+        assert_eq!(session.send("P13=3c"), "OK");
+        assert_eq!(session.send("p13"), "3c");
+    }
+
+    #[test]
+    fn addresses_past_the_end_of_memory_are_rejected() {
+        let mut session = Session::connect();
+        assert_eq!(session.send("P10=ff0f"), "OK");
+        assert_eq!(session.send("P10=0010"), "E01");
+        assert_eq!(session.send("P11=ffff"), "E01");
+        assert_eq!(session.send("p10"), "ff0f");
+        let registers = format!("{}{}", "01".repeat(16), "00100002");
+        assert_eq!(session.send(&format!("G{}", registers)), "E01");
+        assert_eq!(session.send("p0"), "00", "nothing is written");
+        assert_eq!(session.send("p10"), "ff0f");
+    }
+
+    #[test]
+    fn breakpoints_continue_and_step() {
+        let mut session = Session::connect();
+        assert_eq!(session.send("Z0,204,2"), "OK");
+        assert_eq!(session.send("c"), "S05");
+        assert_eq!(session.send("p11"), "0402");
+        assert_eq!(session.send("p0"), "06");
+        assert_eq!(session.send("s"), "S05");
+        assert_eq!(session.send("p11"), "0202");
+        assert_eq!(session.send("s"), "S05");
+        assert_eq!(session.send("p0"), "07");
+        assert_eq!(session.send("z0,204,2"), "OK");
+    }
+
+    #[test]
+    fn detach_lets_the_emulator_run() {
+        let mut session = Session::connect();
+        assert_eq!(session.send("Z0,204,2"), "OK");
+        assert_eq!(session.send("D"), "OK");
+        assert!(!session.debugger.is_halted());
+        for _ in 0..10 {
+            session.debugger.run_frame(&mut session.emu, 10);
+        }
+        assert!(!session.debugger.is_halted());
+        assert!(session.emu.registers()[0] > 10);
+    }
+
+    #[test]
+    fn target_description() {
+        let mut session = Session::connect();
+        assert!(session
+            .send("qSupported:multiprocess+")
+            .contains("qXfer:features:read+"));
+        let first = session.send("qXfer:features:read:target.xml:0,20");
+        assert_eq!(first, format!("m{}", &TARGET_XML[..0x20]));
+        let whole = session.send("qXfer:features:read:target.xml:0,fff");
+        assert_eq!(whole, format!("l{}", TARGET_XML));
+        assert!(whole.contains(r#"<reg name="st" bitsize="8""#));
+        assert_eq!(session.send("qXfer:features:read:other.xml:0,fff"), "E00");
+    }
+}
@@ -0,0 +1,52 @@
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::Keycode;
+
+use crate::keypad::HostKey;
+
+/// Emulator controls, bound to keys that aren't used by the keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    ToggleRecording,
+    Screenshot,
+    ToggleOverlay,
+    Reset,
+    HardReset,
+    RomBrowser,
+    SaveSettings,
+    MemoryViewer,
+    Inspector,
+    SpriteViewer,
+    Pause,
+    AdvanceFrame,
+    FastForward,
+    ToggleSlowMotion,
+}
+
+impl Hotkey {
+    /// Get the hotkey bound to the keyboard key, if any
+    pub fn from_key(key: HostKey) -> Option<Self> {
+        match key {
+            HostKey::Function(1) => Some(Self::ToggleOverlay),
+            HostKey::Function(2) => Some(Self::Reset),
+            HostKey::Function(3) => Some(Self::HardReset),
+            HostKey::Function(4) => Some(Self::RomBrowser),
+            HostKey::Function(5) => Some(Self::SaveSettings),
+            HostKey::Function(6) => Some(Self::MemoryViewer),
+            HostKey::Function(7) => Some(Self::Inspector),
+            HostKey::Function(8) => Some(Self::SpriteViewer),
+            HostKey::Char('p') => Some(Self::Pause),
+            HostKey::Char('n') => Some(Self::AdvanceFrame),
+            HostKey::Tab => Some(Self::FastForward),
+            HostKey::Char('m') => Some(Self::ToggleSlowMotion),
+            HostKey::Function(9) => Some(Self::ToggleRecording),
+            HostKey::Function(12) => Some(Self::Screenshot),
+            _ => None,
+        }
+    }
+
+    /// Get the hotkey bound to the SDL keycode, if any
+    #[cfg(feature = "sdl")]
+    pub fn from_keycode(keycode: Keycode) -> Option<Self> {
+        HostKey::from_keycode(keycode).and_then(Self::from_key)
+    }
+}
@@ -0,0 +1,113 @@
+use sdl2::pixels::Color;
+use sdl2::VideoSubsystem;
+
+use crate::debug_window::{DebugWindow, HIGHLIGHT_COLOR, TEXT_COLOR};
+use crate::emulator::Emulator;
+use crate::opcode::Opcode;
+use crate::symbols::Symbols;
+use crate::text::{draw_text, GLYPH_HEIGHT};
+
+const TEXT_SCALE: u32 = 2;
+const MARGIN: i32 = 8;
+const LINE_HEIGHT: i32 = ((GLYPH_HEIGHT + 3) * TEXT_SCALE) as i32;
+const REGISTERS_PER_LINE: usize = 4;
+const STACK_LINES: usize = 16; // enough for the 16 levels of the original interpreter
+const LINES: i32 = 11 + STACK_LINES as i32;
+
+const WIDTH: u32 = 420;
+const HEIGHT: u32 = (MARGIN * 2 + LINES * LINE_HEIGHT) as u32;
+
+/// Window showing the registers, timers, stack and the next instruction to run
+pub struct Inspector {
+    window: DebugWindow,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self {
+            window: DebugWindow::new("Chip8-Emulator - Registers", WIDTH, HEIGHT),
+        }
+    }
+
+    pub fn toggle(&mut self, video: &VideoSubsystem) -> Result<(), String> {
+        self.window.toggle(video)
+    }
+
+    pub fn close(&mut self) {
+        self.window.close();
+    }
+
+    /// Tells if events for the window with `window_id` belong to the inspector
+    pub fn owns(&self, window_id: u32) -> bool {
+        self.window.owns(window_id)
+    }
+
+    /// Redraw the window with the current state of `emu`, if it's open, with addresses
+    /// labelled from `symbols`
+    pub fn draw(&mut self, emu: &Emulator, symbols: &Symbols) -> Result<(), String> {
+        let Some(canvas) = self.window.canvas_to_draw() else {
+            return Ok(());
+        };
+        for (line, (color, text)) in lines(emu, symbols).iter().enumerate() {
+            canvas.set_draw_color(*color);
+            let y = MARGIN + line as i32 * LINE_HEIGHT;
+            draw_text(canvas, text, MARGIN, y, TEXT_SCALE)?;
+        }
+        canvas.present();
+        Ok(())
+    }
+}
+
+/// Lines of text to show for the state of `emu`, with the colour to draw them in
+fn lines(emu: &Emulator, symbols: &Symbols) -> Vec<(Color, String)> {
+    let instruction = emu.instruction_at(emu.pc());
+    let mut lines = vec![
+        (
+            HIGHLIGHT_COLOR,
+            format!(
+                "{}: {:04X}  {}",
+                symbols.describe(emu.pc()),
+                instruction,
+                Opcode::decode(instruction)
+            ),
+        ),
+        (
+            TEXT_COLOR,
+            format!("PC {:03X}  I {:03X}", emu.pc(), emu.i()),
+        ),
+        (
+            TEXT_COLOR,
+            format!("DT {:02X}  ST {:02X}", emu.delay_timer(), emu.sound_timer()),
+        ),
+        (TEXT_COLOR, format!("CYCLES {}", emu.cycles())),
+        (TEXT_COLOR, String::new()),
+    ];
+
+    for (row, values) in emu.registers().chunks(REGISTERS_PER_LINE).enumerate() {
+        let registers: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{:X} {:02X}", row * REGISTERS_PER_LINE + i, value))
+            .collect();
+        lines.push((TEXT_COLOR, registers.join("  ")));
+    }
+    lines.push((TEXT_COLOR, String::new()));
+
+    lines.push((HIGHLIGHT_COLOR, format!("STACK ({})", emu.stack().len())));
+    if emu.stack().is_empty() {
+        lines.push((TEXT_COLOR, "EMPTY".to_string()));
+    }
+    // most recent call first, return addresses point just after the CALL instruction
+    for (depth, address) in emu.stack().iter().enumerate().rev().take(STACK_LINES) {
+        lines.push((
+            TEXT_COLOR,
+            format!(
+                "{:2}: RETURN TO {:03X}, CALL AT {}",
+                depth,
+                address,
+                symbols.describe(address.wrapping_sub(2))
+            ),
+        ));
+    }
+    lines
+}
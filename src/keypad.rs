@@ -1,57 +1,142 @@
+#[cfg(feature = "sdl")]
 use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
 
 pub const NUM_KEYS: usize = 16;
 // CHIP8 usually used on computers with hexidecimal keypads
 
+/// Extra keys for games where it's known which CHIP8 keys are used for moving and
+/// actions. The arrow keys are used for directions, space for A and left shift for B
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyMap {
+    pub up: Option<u8>,
+    pub down: Option<u8>,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub a: Option<u8>,
+    pub b: Option<u8>,
+}
+
+/// A key on the computer's keyboard, whichever frontend it was pressed in. Letters are
+/// lowercase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKey {
+    Char(char),
+    Function(u8), // F1 to F12
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))] // terminals don't send Shift alone
+    Shift,
+    Tab,
+}
+
+impl HostKey {
+    /// The key for an SDL keycode, None for keys the emulator doesn't use
+    #[cfg(feature = "sdl")]
+    pub fn from_keycode(keycode: Keycode) -> Option<Self> {
+        let key = match keycode {
+            Keycode::UP => Self::Up,
+            Keycode::DOWN => Self::Down,
+            Keycode::LEFT => Self::Left,
+            Keycode::RIGHT => Self::Right,
+            Keycode::SPACE => Self::Space,
+            Keycode::LSHIFT => Self::Shift,
+            Keycode::TAB => Self::Tab,
+            _ => {
+                let name = keycode.name();
+                if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                    Self::Function(number)
+                } else {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c.is_ascii_alphanumeric() => {
+                            Self::Char(c.to_ascii_lowercase())
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+        };
+        Some(key)
+    }
+}
+
 pub struct Keypad {
     keys: [bool; NUM_KEYS],
+    key_map: KeyMap,
 }
 
 impl Keypad {
     pub fn new() -> Self {
         Self {
             keys: [false; NUM_KEYS],
+            key_map: KeyMap::default(),
         }
     }
 
+    /// Use the extra keys from `key_map` on top of the standard keypad layout
+    pub fn set_key_map(&mut self, key_map: KeyMap) {
+        self.key_map = key_map;
+    }
+
     /// Get the current keyboard state
     pub fn get_keys(&self) -> &[bool; NUM_KEYS] {
         &self.keys
     }
 
-    /// Set the key corresponding to the given keycode to true
-    pub fn key_down(&mut self, keycode: Keycode) {
-        if let Some(index) = Self::key_mapping(keycode) {
+    /// Release every key, keeping the key map
+    pub fn release_all(&mut self) {
+        self.keys = [false; NUM_KEYS];
+    }
+
+    /// Press or release CHIP8 key `key` directly, e.g. from an input movie
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keys[key as usize % NUM_KEYS] = pressed;
+    }
+
+    /// Set the CHIP8 key corresponding to the keyboard key to true
+    pub fn key_down(&mut self, key: HostKey) {
+        if let Some(index) = self.key_mapping(key) {
             self.keys[index as usize] = true;
         }
     }
 
-    /// Set the key corresponding to the given keycode to false
-    pub fn key_up(&mut self, keycode: Keycode) {
-        if let Some(index) = Self::key_mapping(keycode) {
+    /// Set the CHIP8 key corresponding to the keyboard key to false
+    pub fn key_up(&mut self, key: HostKey) {
+        if let Some(index) = self.key_mapping(key) {
             self.keys[index as usize] = false;
         }
     }
 
     /// Get mapping of computer keyboard key to CHIP8 key
-    fn key_mapping(keycode: Keycode) -> Option<u8> {
-        match keycode {
-            Keycode::NUM_1 => Some(0x1),
-            Keycode::NUM_2 => Some(0x2),
-            Keycode::NUM_3 => Some(0x3),
-            Keycode::NUM_4 => Some(0xC),
-            Keycode::Q => Some(0x4),
-            Keycode::W => Some(0x5),
-            Keycode::E => Some(0x6),
-            Keycode::R => Some(0xD),
-            Keycode::A => Some(0x7),
-            Keycode::S => Some(0x8),
-            Keycode::D => Some(0x9),
-            Keycode::F => Some(0xE),
-            Keycode::Z => Some(0xA),
-            Keycode::X => Some(0x0),
-            Keycode::C => Some(0xB),
-            Keycode::V => Some(0xF),
+    fn key_mapping(&self, key: HostKey) -> Option<u8> {
+        match key {
+            HostKey::Char('1') => Some(0x1),
+            HostKey::Char('2') => Some(0x2),
+            HostKey::Char('3') => Some(0x3),
+            HostKey::Char('4') => Some(0xC),
+            HostKey::Char('q') => Some(0x4),
+            HostKey::Char('w') => Some(0x5),
+            HostKey::Char('e') => Some(0x6),
+            HostKey::Char('r') => Some(0xD),
+            HostKey::Char('a') => Some(0x7),
+            HostKey::Char('s') => Some(0x8),
+            HostKey::Char('d') => Some(0x9),
+            HostKey::Char('f') => Some(0xE),
+            HostKey::Char('z') => Some(0xA),
+            HostKey::Char('x') => Some(0x0),
+            HostKey::Char('c') => Some(0xB),
+            HostKey::Char('v') => Some(0xF),
+            HostKey::Up => self.key_map.up,
+            HostKey::Down => self.key_map.down,
+            HostKey::Left => self.key_map.left,
+            HostKey::Right => self.key_map.right,
+            HostKey::Space => self.key_map.a,
+            HostKey::Shift => self.key_map.b,
             _ => None,
         }
     }
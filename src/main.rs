@@ -1,26 +1,121 @@
+mod analyzer;
+#[cfg(feature = "sdl")]
+mod browser;
+mod config;
+mod controls;
+mod coverage;
+mod database;
+#[cfg(feature = "sdl")]
+mod debug_window;
+mod debugger;
+mod difftest;
 mod emulator;
+mod expression;
+mod gdb;
+mod hotkeys;
+#[cfg(feature = "sdl")]
+mod inspector;
 mod keypad;
+#[cfg(feature = "sdl")]
+mod memory_viewer;
+mod movie;
 mod opcode;
+#[cfg(feature = "sdl")]
+mod overlay;
+mod palette;
+mod profiler;
+mod recorder;
+mod screenshot;
+mod settings;
 mod sound;
+#[cfg(feature = "sdl")]
+mod sprite_viewer;
+mod symbols;
+mod terminal;
+#[cfg(feature = "sdl")]
+mod text;
+mod tracer;
+mod watcher;
+mod wav;
 
+use crate::analyzer::Analyzer;
+#[cfg(feature = "sdl")]
+use crate::browser::RomBrowser;
+use crate::controls::{Controls, FrameBudget};
+use crate::coverage::Coverage;
+use crate::database::{RomDatabase, RomInfo};
+use crate::debugger::{parse_breakpoint, Debugger, StopReason};
+use crate::difftest::compare_trace;
+#[cfg(feature = "sdl")]
 use crate::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::sound::SoundSystem;
+use crate::expression::Expression;
+use crate::gdb::GdbStub;
+use crate::hotkeys::Hotkey;
+#[cfg(feature = "sdl")]
+use crate::inspector::Inspector;
+#[cfg(feature = "sdl")]
+use crate::keypad::HostKey;
+use crate::keypad::Keypad;
+#[cfg(feature = "sdl")]
+use crate::memory_viewer::MemoryViewer;
+use crate::movie::Movie;
+#[cfg(feature = "sdl")]
+use crate::overlay::Overlay;
+use crate::palette::parse_color;
+use crate::profiler::Profiler;
+use crate::recorder::{parse_scale, Recorder};
+use crate::screenshot::{save_screenshot, timestamped_file};
+use crate::settings::{RomSettings, SettingsSources, DEFAULT_INSTRUCTIONS_PER_FRAME};
+#[cfg(feature = "sdl")]
+use crate::sound::open_audio;
+use crate::sound::{AudioBackend, NullAudio, DEFAULT_FREQUENCY, DEFAULT_VOLUME};
+#[cfg(feature = "sdl")]
+use crate::sprite_viewer::SpriteViewer;
+use crate::symbols::Symbols;
+use crate::terminal::{Terminal, TerminalBell, TerminalEvent};
+use crate::tracer::{parse_class, parse_range, TraceFilter, Tracer};
+use crate::watcher::FileWatcher;
+use crate::wav::WavRecorder;
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
 use emulator::Emulator;
-use log::{debug, info};
-use sdl2::event::Event;
+use log::{debug, error, info};
+#[cfg(feature = "sdl")]
+use sdl2::event::{Event, WindowEvent};
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::Keycode;
+#[cfg(feature = "sdl")]
 use sdl2::pixels::{Color, PixelFormatEnum};
+#[cfg(feature = "sdl")]
 use sdl2::rect::Rect;
+#[cfg(feature = "sdl")]
 use sdl2::render::TextureAccess;
-use std::time::Duration;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Size of each emulator pixel in the window
+const PIXEL_SIZE: usize = 16;
+
+/// Base name for screenshots and recordings made before a ROM has been loaded
+#[cfg(feature = "sdl")]
+const DEFAULT_OUTPUT_NAME: &str = "chip8";
+
+/// Wall clock time of a 60Hz frame
+const FRAME_DURATION: Duration = Duration::from_micros(1_000_000 / 60);
 
 #[derive(Parser, Debug)]
 #[command(version, long_about = None)]
 struct Args {
-    /// Name of the file to run in the emulator
-    #[arg(short, long)]
-    filename: String,
+    /// Name of the file to run in the emulator. If not given, the ROM browser opens on start
+    #[arg(short, long, required_if_eq_any([("headless", "true"), ("tui", "true")]))]
+    filename: Option<String>,
+
+    /// Directory listed in the ROM browser (F4), defaults to the directory of the ROM,
+    /// or the current directory
+    #[arg(long)]
+    rom_dir: Option<String>,
 
     /// Older versions of the CHIP-8 used to set the X register to the value of the Y register
     /// on shift operations. Newer versions shift X in place, if you need the older behaviour
@@ -39,38 +134,526 @@ struct Args {
     /// and common behaviour is to not do this, so we don't do it by default
     #[arg(short, long)]
     modify_i_in_load_and_store: bool,
+
+    /// Colour of pixels that are on, as hex e.g. #FFFFFF
+    #[arg(long, value_parser = parse_color, default_value = "#FFFFFF")]
+    foreground: [u8; 3],
+
+    /// Colour of pixels that are off, as hex e.g. #000000
+    #[arg(long, value_parser = parse_color, default_value = "#000000")]
+    background: [u8; 3],
+
+    /// Directory with the files of the community CHIP-8 database (programs.json,
+    /// sha1-hashes.json and platforms.json). By default they're loaded from
+    /// ~/.local/share/chip8-emulator/database if they're there, or the bundled copy.
+    /// ROMs found in the database get their quirks, speed, colours and keys picked
+    /// automatically, unless they're set on the command line
+    #[arg(long)]
+    database: Option<String>,
+
+    /// Don't look ROMs up in the database
+    #[arg(long)]
+    no_database: bool,
+
+    /// Pitch of the beep in Hz
+    #[arg(long, default_value_t = DEFAULT_FREQUENCY)]
+    frequency: f32,
+
+    /// Volume of the beep, from 0 to 1
+    #[arg(long, default_value_t = DEFAULT_VOLUME)]
+    volume: f32,
+
+    /// Run without sound, by default sound is also turned off automatically if no audio
+    /// device can be opened
+    #[arg(long)]
+    no_audio: bool,
+
+    /// Seed for random numbers, so runs are repeatable. By default a new random seed is
+    /// picked every time the emulator starts or resets
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Reload the ROM whenever the file changes, keeping the quirks it's running with
+    #[arg(long)]
+    hot_reload: bool,
+
+    /// Keep the keys that are held down when the ROM is hot reloaded, instead of
+    /// starting with all keys released
+    #[arg(long)]
+    keep_keys_on_reload: bool,
+
+    /// Run without opening a window or an audio device, running frames as fast as possible
+    /// rather than at 60Hz
+    #[arg(long)]
+    headless: bool,
+
+    /// Run in the terminal instead of a window, drawing the screen with half block
+    /// characters and beeping with the terminal bell, e.g. over SSH
+    #[arg(long, conflicts_with = "headless")]
+    tui: bool,
+
+    /// Listen for GDB, or another debugger speaking its remote protocol, on this localhost
+    /// port. The emulator waits for the debugger to connect before running
+    #[arg(long)]
+    gdb: Option<u16>,
+
+    /// Number of instructions GDB can step back through with reverse-stepi and
+    /// reverse-continue, 0 to not keep any history
+    #[arg(long, default_value_t = 10000)]
+    history: usize,
+
+    /// Stop at this address, only when the condition after `if` is true if there is one,
+    /// e.g. `2A0 if V3 == 0x10 && HITS > 2`. Can be given more than once
+    #[arg(long = "break", id = "break", value_parser = parse_breakpoint)]
+    breakpoints: Vec<(u16, Option<Expression>)>,
+
+    /// Stop whenever the value of this expression changes, e.g. `V3` or `[0x300]`. Can be
+    /// given more than once
+    #[arg(long = "watch", id = "watch", value_parser = Expression::parse)]
+    watchpoints: Vec<Expression>,
+
+    /// Label addresses in backtraces and the register inspector with this symbol file from
+    /// the assembler, a line for each label with its address, e.g. `draw_score = 0x2A0`
+    #[arg(long)]
+    symbols: Option<String>,
+
+    /// Write a line for every instruction executed to this file: the cycle, PC, opcode,
+    /// the instruction in assembly, the registers it changed and I
+    #[arg(long)]
+    trace: Option<String>,
+
+    /// Only trace instructions in this address range, e.g. 200-2FF. Can be given more than once
+    #[arg(long, value_parser = parse_range)]
+    trace_range: Vec<RangeInclusive<u16>>,
+
+    /// Only trace instructions of these classes, the first hex digit of the opcode, e.g. D,F
+    #[arg(long, value_parser = parse_class, value_delimiter = ',')]
+    trace_class: Vec<u8>,
+
+    /// Count how many times each address and kind of instruction is executed, and write
+    /// a report with the hot spots and a heatmap of the disassembly to this file on exit
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Map which bytes of memory are executed, read as data and written, and write the map
+    /// with how much of the program was used to this file on exit
+    #[arg(long)]
+    coverage: Option<String>,
+
+    /// Look out for self-modifying code, jumps into data, calls more than 16 deep, returns
+    /// with an empty stack and memory accesses past the end of RAM, and write what was
+    /// found, with the PC and a backtrace, to this file on exit
+    #[arg(long)]
+    analyze: Option<String>,
+
+    /// Press keys from this movie file when running headless, a line for each press or
+    /// release with the frame, the CHIP8 key in hex and down or up, e.g. `120 5 down`
+    #[arg(long)]
+    movie: Option<String>,
+
+    /// Run headless, checking every instruction against this trace from another emulator
+    /// in the format of --trace, and stop at the first one that differs. Steps the emulator
+    /// directly, so it can't be combined with the debugging and recording options
+    #[arg(
+        long,
+        requires = "headless",
+        conflicts_with_all = [
+            "tui", "gdb", "break", "watch", "trace", "profile", "coverage", "analyze", "wav",
+            "record", "screenshot",
+        ]
+    )]
+    compare_trace: Option<String>,
+
+    /// Number of 60Hz frames to run for when running headless
+    #[arg(long, default_value_t = 600)]
+    frames: u32,
+
+    /// Number of instructions executed per 60Hz frame
+    #[arg(long, default_value_t = DEFAULT_INSTRUCTIONS_PER_FRAME)]
+    instructions_per_frame: u32,
+
+    /// How many times faster than normal to run while fast-forward (Tab) is held,
+    /// 0 runs as fast as possible
+    #[arg(long, default_value_t = 0.0)]
+    fast_forward_speed: f64,
+
+    /// How fast to run compared to normal when slow motion (M) is on
+    #[arg(long, default_value_t = 0.25)]
+    slow_motion_speed: f64,
+
+    /// Write the audio of a headless run to this WAV file. Use --record-audio to record
+    /// the sound of other runs
+    #[arg(long, requires = "headless", conflicts_with = "tui")]
+    wav: Option<String>,
+
+    /// Save a screenshot of the end of a headless run to this PNG file, along with a copy
+    /// at the window's scale. F12 takes a screenshot next to the ROM while running
+    #[arg(long)]
+    screenshot: Option<String>,
+
+    /// Record the screen from startup to this file, either a .gif, at 50 frames per second
+    /// and dropping every sixth frame, or a .y4m video with all 60. Recording can also be
+    /// toggled with F9, which records to a GIF next to the ROM
+    #[arg(long)]
+    record: Option<String>,
+
+    /// Size of each emulator pixel in recordings, independent of the window size
+    #[arg(long, default_value_t = 4, value_parser = parse_scale)]
+    record_scale: usize,
+
+    /// Also record the audio, to a WAV file with the same name as the recording
+    #[arg(long)]
+    record_audio: bool,
 }
 
-fn main() -> Result<(), String> {
-    let args = Args::parse();
+impl Args {
+    /// Per ROM settings from the command line. If `explicit` is true only the flags that
+    /// were actually passed, or set by environment variables, are included, otherwise
+    /// flags that weren't passed have their defaults, which may come from the config file
+    fn rom_settings(&self, matches: &ArgMatches, explicit: bool) -> RomSettings {
+        let passed = |id: &str| {
+            !explicit
+                || matches!(
+                    matches.value_source(id),
+                    Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+                )
+        };
+        RomSettings {
+            shift_with_y: passed("shift_with_y").then_some(self.shift_with_y),
+            jump_with_x: passed("jump_with_x").then_some(self.jump_with_x),
+            modify_i_in_load_and_store: passed("modify_i_in_load_and_store")
+                .then_some(self.modify_i_in_load_and_store),
+            instructions_per_frame: passed("instructions_per_frame")
+                .then_some(self.instructions_per_frame),
+            foreground: passed("foreground").then_some(self.foreground),
+            background: passed("background").then_some(self.background),
+            frequency: passed("frequency").then_some(self.frequency),
+            volume: passed("volume").then_some(self.volume),
+            key_map: None,
+        }
+    }
+
+    /// Debugger for the emulator, halted if waiting for GDB, with the breakpoints,
+    /// watchpoints, tracer, profiler, coverage map and analyzer that were asked for
+    fn debugger(&self) -> Result<Debugger, String> {
+        let mut debugger = Debugger::new(self.gdb.is_some());
+        let symbols = match &self.symbols {
+            Some(file) => Symbols::load(file)?,
+            None => Symbols::default(),
+        };
+        debugger.set_symbols(symbols.clone());
+        for (address, condition) in &self.breakpoints {
+            debugger.add_breakpoint(*address, condition.clone());
+        }
+        for expression in &self.watchpoints {
+            debugger.add_watchpoint(expression.clone());
+        }
+        if let Some(file) = &self.trace {
+            let filter = TraceFilter {
+                ranges: self.trace_range.clone(),
+                classes: self.trace_class.clone(),
+            };
+            debugger.set_tracer(Tracer::create(file, filter)?);
+        }
+        if self.profile.is_some() {
+            debugger.set_profiler(Profiler::new());
+        }
+        if self.coverage.is_some() {
+            debugger.set_coverage(Coverage::new());
+        }
+        if self.analyze.is_some() {
+            debugger.set_analyzer(Analyzer::new(symbols));
+        }
+        Ok(debugger)
+    }
+
+    /// Write the profiler's report, the coverage map and the analysis, if asked for
+    fn save_reports(&self, debugger: &Debugger, emu: &Emulator) -> Result<(), String> {
+        if let (Some(file), Some(profiler)) = (&self.profile, debugger.profiler()) {
+            profiler.save(file, emu)?;
+            info!("Wrote profile to {}", file);
+        }
+        if let (Some(file), Some(coverage)) = (&self.coverage, debugger.coverage()) {
+            coverage.save(file, emu)?;
+            info!("{}", coverage.summary(emu));
+            info!("Wrote coverage map to {}", file);
+        }
+        if let (Some(file), Some(analyzer)) = (&self.analyze, debugger.analyzer()) {
+            analyzer.save(file)?;
+            info!("Wrote analysis to {}", file);
+        }
+        Ok(())
+    }
+
+    /// Where settings for each ROM come from
+    fn settings_sources(&self, matches: &ArgMatches) -> Result<SettingsSources, String> {
+        let database = if self.no_database {
+            None
+        } else {
+            Some(RomDatabase::load(self.database.as_deref())?)
+        };
+        Ok(SettingsSources {
+            command_line: self.rom_settings(matches, true),
+            database,
+            defaults: self.rom_settings(matches, false),
+        })
+    }
+}
 
-    dotenvy::dotenv().map_err(|e| e.to_string())?;
+fn main() -> Result<(), String> {
+    // a .env file is optional, it only sets environment variables if there is one
+    dotenvy::dotenv().ok();
     env_logger::init();
 
-    info!("Running {}", args.filename);
+    let matches = config::layered(Args::command())?.get_matches();
+    let args = Args::from_arg_matches(&matches).map_err(|e| e.to_string())?;
 
     debug!("Running with {:?}", args);
 
-    let pixel_size = 16;
+    let sources = args.settings_sources(&matches)?;
+
+    if args.headless {
+        return run_headless(&args, &sources);
+    }
+    if args.tui {
+        return run_terminal(&args, &sources);
+    }
+    run_window(&args, &sources)
+}
+
+/// The emulator and what drives it, shared by the window and terminal frontends: the
+/// settings of the ROM it's running, the debugger, GDB, the speed controls, the sound and
+/// the recording
+struct Machine {
+    emu: Emulator,
+    settings: RomSettings,
+    debugger: Debugger,
+    gdb: Option<GdbStub>,
+    controls: Controls,
+    sound: Box<dyn AudioBackend>,
+    recorder: Option<Recorder>,
+}
+
+impl Machine {
+    /// Start running `rom`, or wait for one to be loaded if None. Also returns what the ROM
+    /// database knows about the ROM
+    fn start(
+        args: &Args,
+        sources: &SettingsSources,
+        rom: Option<&str>,
+        mut sound: Box<dyn AudioBackend>,
+    ) -> Result<(Self, Option<RomInfo>), String> {
+        let mut emu = Emulator::new(sources.defaults.quirks(), args.seed);
+        if args.gdb.is_some() {
+            emu.set_history_limit(args.history);
+        }
+        let (settings, rom_info) = match rom {
+            Some(file) => {
+                info!("Running {}", file);
+                load_rom(&mut emu, file, sources)?
+            }
+            None => (sources.defaults.clone(), None),
+        };
+        sound.set_tone(settings.tone());
+        let recorder = match &args.record {
+            Some(file) => Some(Recorder::start(
+                file,
+                args.record_scale,
+                settings.palette(),
+                args.record_audio.then(|| settings.tone()),
+            )?),
+            None => None,
+        };
+        let machine = Self {
+            emu,
+            settings,
+            debugger: args.debugger()?,
+            gdb: args.gdb.map(GdbStub::listen).transpose()?,
+            controls: Controls::new(args.fast_forward_speed, args.slow_motion_speed),
+            sound,
+            recorder,
+        };
+        Ok((machine, rom_info))
+    }
+
+    /// Switch to running `file`, returning what the ROM database knows about it
+    #[cfg(feature = "sdl")]
+    fn load(&mut self, file: &str, sources: &SettingsSources) -> Result<Option<RomInfo>, String> {
+        let (settings, rom_info) = load_rom(&mut self.emu, file, sources)?;
+        info!("Running {}", file);
+        self.sound.set_tone(settings.tone());
+        self.settings = settings;
+        Ok(rom_info)
+    }
 
+    /// Reload `file` after it changed on disk, returning the message to show
+    fn reload(&mut self, file: &str, keep_keys: bool) -> &'static str {
+        match reload_rom(&mut self.emu, file, keep_keys) {
+            Ok(()) => {
+                info!("Reloaded {}", file);
+                "ROM RELOADED"
+            }
+            Err(e) => {
+                error!("Could not reload ROM: {}", e);
+                "RELOAD FAILED"
+            }
+        }
+    }
+
+    /// Carry out one of the hotkeys every frontend has, returning the message to show if
+    /// there is one. Screenshots are named after `output_name`, and settings are saved
+    /// next to `rom`
+    fn handle_hotkey(
+        &mut self,
+        hotkey: Hotkey,
+        rom: Option<&str>,
+        output_name: &str,
+    ) -> Option<&'static str> {
+        match hotkey {
+            Hotkey::Screenshot => {
+                let file = timestamped_file(output_name, "png");
+                let palette = self.settings.palette();
+                match save_screenshot(&file, self.emu.screen(), &palette, PIXEL_SIZE) {
+                    Ok(files) => {
+                        info!("Saved screenshot to {}", files.join(", "));
+                        Some("SCREENSHOT SAVED")
+                    }
+                    Err(e) => {
+                        error!("Could not save screenshot: {}", e);
+                        Some("SCREENSHOT FAILED")
+                    }
+                }
+            }
+            Hotkey::Reset => {
+                self.emu.reset();
+                Some("RESET")
+            }
+            Hotkey::HardReset => {
+                self.emu.hard_reset();
+                Some("HARD RESET")
+            }
+            Hotkey::SaveSettings => match rom.map(|file| self.settings.save_sidecar(file)) {
+                Some(Ok(saved)) => {
+                    info!("Saved settings to {}", saved);
+                    Some("SETTINGS SAVED")
+                }
+                Some(Err(e)) => {
+                    error!("Could not save settings: {}", e);
+                    Some("COULD NOT SAVE SETTINGS")
+                }
+                None => Some("NO ROM LOADED"),
+            },
+            Hotkey::Pause => {
+                self.controls.toggle_pause();
+                None
+            }
+            Hotkey::AdvanceFrame => {
+                self.controls.advance_frame();
+                None
+            }
+            Hotkey::FastForward => {
+                self.controls.fast_forward = true;
+                None
+            }
+            Hotkey::ToggleSlowMotion => {
+                self.controls.slow_motion = !self.controls.slow_motion;
+                Some(if self.controls.slow_motion {
+                    "SLOW MOTION ON"
+                } else {
+                    "SLOW MOTION OFF"
+                })
+            }
+            // handled by the frontends that have them
+            Hotkey::ToggleRecording
+            | Hotkey::ToggleOverlay
+            | Hotkey::RomBrowser
+            | Hotkey::MemoryViewer
+            | Hotkey::Inspector
+            | Hotkey::SpriteViewer => None,
+        }
+    }
+
+    /// Answer GDB and run the frames that are due, none while `blocked`, for up to a
+    /// frame's worth of wall clock time, then update the sound. Without GDB to carry on
+    /// from breakpoints, watchpoints and faults they pause instead, and the message to
+    /// show for the one that was hit is returned
+    fn run_frames(&mut self, blocked: bool) -> Result<Option<&'static str>, String> {
+        if let Some(gdb) = self.gdb.as_mut() {
+            gdb.poll(&mut self.emu, &mut self.debugger);
+        }
+
+        let stopped = self.controls.paused || blocked || self.debugger.is_halted();
+        let frames = match self.controls.frames_due() {
+            _ if blocked => 0,
+            // steps from the debugger run even while paused
+            _ if self.debugger.is_stepping() => 1,
+            FrameBudget::Frames(frames) => frames,
+            FrameBudget::Unthrottled => u32::MAX,
+        };
+        let frame_start = Instant::now();
+        let mut frames_run = 0;
+        while frames_run < frames && frame_start.elapsed() < FRAME_DURATION {
+            let instructions_per_frame = self.settings.instructions_per_frame();
+            if !self
+                .debugger
+                .run_frame(&mut self.emu, instructions_per_frame)
+            {
+                break;
+            }
+            if let Some(recorder) = self.recorder.as_mut() {
+                recorder.record_frame(self.emu.screen(), self.emu.sound_timer())?;
+            }
+            frames_run += 1;
+        }
+
+        let mut message = None;
+        if self.gdb.is_none() && self.debugger.is_halted() {
+            self.controls.paused = true;
+            self.debugger.resume();
+            message = Some(match self.debugger.stop_reason() {
+                StopReason::Watchpoint => "WATCHPOINT",
+                StopReason::Fault => "FAULT",
+                _ => "BREAKPOINT",
+            });
+        }
+
+        self.sound
+            .handle_sound_timer(if stopped { &0 } else { self.emu.sound_timer() });
+        Ok(message)
+    }
+
+    /// Save the recording and write the reports that were asked for
+    fn finish(self, args: &Args) -> Result<(), String> {
+        if let Some(recorder) = self.recorder {
+            let file = recorder.finish()?;
+            info!("Saved recording to {}", file);
+        }
+        args.save_reports(&self.debugger, &self.emu)
+    }
+}
+
+/// Run in a window with SDL, along with the debug windows, until it's closed
+#[cfg(feature = "sdl")]
+fn run_window(args: &Args, sources: &SettingsSources) -> Result<(), String> {
     let context = sdl2::init()?;
     let video_subsystem = context.video()?;
     let window = video_subsystem
         .window(
             "Chip8-Emulator",
-            (SCREEN_WIDTH * pixel_size) as u32,
-            (SCREEN_HEIGHT * pixel_size) as u32,
+            (SCREEN_WIDTH * PIXEL_SIZE) as u32,
+            (SCREEN_HEIGHT * PIXEL_SIZE) as u32,
         )
         .build()
         .map_err(|e| e.to_string())?;
 
+    let main_window_id = window.id();
     let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
 
     let screen_area = Rect::new(
         0,
         0,
-        (SCREEN_WIDTH * pixel_size) as u32,
-        (SCREEN_HEIGHT * pixel_size) as u32,
+        (SCREEN_WIDTH * PIXEL_SIZE) as u32,
+        (SCREEN_HEIGHT * PIXEL_SIZE) as u32,
     );
 
     let mut running = true;
@@ -80,57 +663,197 @@ fn main() -> Result<(), String> {
 
     let mut texture = texture_creator
         .create_texture(
-            PixelFormatEnum::RGB332,
+            PixelFormatEnum::RGB24,
             TextureAccess::Streaming,
             SCREEN_WIDTH as u32,
             SCREEN_HEIGHT as u32,
         )
         .map_err(|e| e.to_string())?;
 
-    let mut emu = Emulator::new(
-        args.shift_with_y,
-        args.jump_with_x,
-        args.modify_i_in_load_and_store,
-    );
-    emu.load_file(&args.filename);
+    let mut rom_file = args.filename.clone();
+    let sound_system = open_audio(&context, args.no_audio);
+    let (mut machine, rom_info) = Machine::start(args, sources, rom_file.as_deref(), sound_system)?;
+    if let Some(file) = &rom_file {
+        canvas
+            .window_mut()
+            .set_title(&window_title(file, rom_info.as_ref()))
+            .ok();
+    }
+
+    let rom_dir = match (&args.rom_dir, &rom_file) {
+        (Some(dir), _) => Path::new(dir).to_path_buf(),
+        (None, Some(file)) => Path::new(file)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf(),
+        (None, None) => Path::new(".").to_path_buf(),
+    };
+    let mut browser = RomBrowser::new(&rom_dir);
+    if rom_file.is_none() {
+        browser.open();
+    }
 
-    canvas.set_draw_color(Color::BLACK);
+    let [r, g, b] = machine.settings.palette().background;
+    canvas.set_draw_color(Color::RGB(r, g, b));
     canvas.fill_rect(screen_area)?;
     canvas.present();
 
-    let sound_system = SoundSystem::new(context);
+    let mut overlay = Overlay::new();
+    let mut memory_viewer = MemoryViewer::new();
+    let mut inspector = Inspector::new();
+    let mut sprite_viewer = SpriteViewer::new();
+
+    let mut watcher = rom_file
+        .as_ref()
+        .filter(|_| args.hot_reload)
+        .map(|file| FileWatcher::new(file));
 
     while running {
+        let mut picked_rom: Option<String> = None;
         for event in event_pump.poll_iter() {
+            let output_name = rom_file.as_deref().unwrap_or(DEFAULT_OUTPUT_NAME);
             match event {
                 Event::Quit { .. } => {
                     running = false;
                 }
-                Event::KeyDown {
-                    keycode: Some(key), ..
+                // closing a debug window doesn't quit, and with one open closing the
+                // main window doesn't send a Quit event
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Close,
+                    ..
                 } => {
-                    emu.keypad.key_down(key);
+                    if window_id == main_window_id {
+                        running = false;
+                    } else if memory_viewer.owns(window_id) {
+                        memory_viewer.close();
+                    } else if inspector.owns(window_id) {
+                        inspector.close();
+                    } else if sprite_viewer.owns(window_id) {
+                        sprite_viewer.close();
+                    }
+                }
+                Event::KeyDown {
+                    window_id,
+                    keycode: Some(key),
+                    ..
+                } if memory_viewer.owns(window_id) && Hotkey::from_keycode(key).is_none() => {
+                    memory_viewer.handle_key(key, &mut machine.emu, machine.controls.paused)
                 }
+                Event::MouseWheel { window_id, y, .. } if memory_viewer.owns(window_id) => {
+                    memory_viewer.scroll(-y)
+                }
+                Event::KeyDown {
+                    window_id,
+                    keycode: Some(key),
+                    ..
+                } if sprite_viewer.owns(window_id) && Hotkey::from_keycode(key).is_none() => {
+                    sprite_viewer.handle_key(key, &machine.emu)
+                }
+                Event::MouseWheel { window_id, y, .. } if sprite_viewer.owns(window_id) => {
+                    sprite_viewer.scroll(&machine.emu, -y)
+                }
+                Event::DropFile { filename, .. } => picked_rom = Some(filename),
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if browser.is_open() => {
+                    // can only go back to the game if there is one
+                    if matches!(key, Keycode::F4 | Keycode::ESCAPE) && rom_file.is_some() {
+                        browser.close();
+                    } else if let Some(rom) = browser.handle_key(key) {
+                        picked_rom = Some(rom.to_string_lossy().into_owned());
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => match Hotkey::from_keycode(key) {
+                    Some(Hotkey::ToggleRecording) => {
+                        machine.recorder = toggle_recording(
+                            machine.recorder.take(),
+                            output_name,
+                            args,
+                            &machine.settings,
+                            &mut overlay,
+                        )?;
+                    }
+                    Some(Hotkey::ToggleOverlay) => overlay.show_stats = !overlay.show_stats,
+                    Some(Hotkey::RomBrowser) => browser.open(),
+                    Some(Hotkey::MemoryViewer) => memory_viewer.toggle(&video_subsystem)?,
+                    Some(Hotkey::Inspector) => inspector.toggle(&video_subsystem)?,
+                    Some(Hotkey::SpriteViewer) => sprite_viewer.toggle(&video_subsystem)?,
+                    Some(hotkey) => {
+                        let rom = rom_file.as_deref();
+                        if let Some(message) = machine.handle_hotkey(hotkey, rom, output_name) {
+                            overlay.show_message(message);
+                        }
+                    }
+                    None => {
+                        if let Some(key) = HostKey::from_keycode(key) {
+                            machine.emu.keypad.key_down(key)
+                        }
+                    }
+                },
                 Event::KeyUp {
                     keycode: Some(key), ..
-                } => {
-                    emu.keypad.key_up(key);
-                }
+                } => match Hotkey::from_keycode(key) {
+                    Some(Hotkey::FastForward) => machine.controls.fast_forward = false,
+                    Some(_) => {}
+                    None => {
+                        if let Some(key) = HostKey::from_keycode(key) {
+                            machine.emu.keypad.key_up(key)
+                        }
+                    }
+                },
                 _ => {}
             }
         }
-        emu.execute();
-        sound_system.handle_sound_timer(emu.sound_timer());
-        if emu.needs_redraw() {
+
+        if let Some(file) = picked_rom {
+            match machine.load(&file, sources) {
+                Ok(rom_info) => {
+                    browser.close();
+                    canvas
+                        .window_mut()
+                        .set_title(&window_title(&file, rom_info.as_ref()))
+                        .ok();
+                    if args.hot_reload {
+                        watcher = Some(FileWatcher::new(&file));
+                    }
+                    rom_file = Some(file);
+                }
+                Err(e) => {
+                    error!("Could not load ROM: {}", e);
+                    overlay.show_message("COULD NOT LOAD ROM");
+                }
+            }
+        }
+
+        if let (Some(file), true) = (&rom_file, watcher.as_mut().is_some_and(|w| w.changed())) {
+            overlay.show_message(machine.reload(file, args.keep_keys_on_reload));
+        }
+
+        // Nothing to run until a ROM has been picked, and games shouldn't carry on
+        // behind the browser
+        let stopped = machine.controls.paused || browser.is_open() || machine.debugger.is_halted();
+        if let Some(message) = machine.run_frames(browser.is_open())? {
+            overlay.show_message(message);
+        }
+
+        let emu = &mut machine.emu;
+        let overlay_refresh = overlay.needs_refresh(stopped);
+        if emu.needs_redraw() || overlay_refresh {
+            let palette = machine.settings.palette();
             texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
                 for y in 0..SCREEN_HEIGHT {
                     for x in 0..SCREEN_WIDTH {
-                        let offset = y * pitch + x;
-                        buffer[offset] = if emu.screen()[y][x] {
-                            0xFF // white
-                        } else {
-                            0x00 // black
-                        };
+                        let offset = y * pitch + x * 3;
+                        buffer[offset..offset + 3]
+                            .copy_from_slice(&palette.color(emu.screen()[y][x]));
                     }
                 }
             })?;
@@ -141,14 +864,263 @@ fn main() -> Result<(), String> {
                 Some(Rect::new(
                     0,
                     0,
-                    (SCREEN_WIDTH * pixel_size) as u32,
-                    (SCREEN_HEIGHT * pixel_size) as u32,
+                    (SCREEN_WIDTH * PIXEL_SIZE) as u32,
+                    (SCREEN_HEIGHT * PIXEL_SIZE) as u32,
                 )),
             )?;
+            overlay.draw(&mut canvas, &emu.quirks().label(), machine.controls.paused)?;
+            if browser.is_open() {
+                browser.draw(&mut canvas)?;
+            }
             canvas.present();
+            overlay.count_frame(emu.cycles());
         }
-        std::thread::sleep(Duration::new(0, 140000));
+        memory_viewer.draw(emu, machine.controls.paused)?;
+        inspector.draw(emu, machine.debugger.symbols())?;
+        sprite_viewer.draw(emu)?;
+        std::thread::sleep(Duration::from_millis(1));
     }
 
+    machine.finish(args)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_window(_args: &Args, _sources: &SettingsSources) -> Result<(), String> {
+    Err("Built without SDL, run with --tui or --headless".to_string())
+}
+
+/// Stop the recording if there is one, otherwise start a new one next to the ROM
+#[cfg(feature = "sdl")]
+fn toggle_recording(
+    recorder: Option<Recorder>,
+    rom: &str,
+    args: &Args,
+    settings: &RomSettings,
+    overlay: &mut Overlay,
+) -> Result<Option<Recorder>, String> {
+    match recorder {
+        Some(recorder) => {
+            let file = recorder.finish()?;
+            info!("Saved recording to {}", file);
+            overlay.show_message("RECORDING SAVED");
+            Ok(None)
+        }
+        None => {
+            let file = timestamped_file(rom, "gif");
+            let audio = args.record_audio.then(|| settings.tone());
+            match Recorder::start(&file, args.record_scale, settings.palette(), audio) {
+                Ok(recorder) => {
+                    info!("Recording to {}", file);
+                    overlay.show_message("RECORDING");
+                    Ok(Some(recorder))
+                }
+                Err(e) => {
+                    error!("Could not start recording: {}", e);
+                    overlay.show_message("RECORDING FAILED");
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+/// Load `file` into the emulator and switch to the settings for it, which are returned
+/// along with what the ROM database knows about it. The keypad is reset
+fn load_rom(
+    emu: &mut Emulator,
+    file: &str,
+    sources: &SettingsSources,
+) -> Result<(RomSettings, Option<RomInfo>), String> {
+    emu.load_file(file)?;
+    let (settings, rom_info) = sources.settings_for(file, emu.rom());
+    emu.set_quirks(settings.quirks());
+    emu.keypad = Keypad::new();
+    emu.keypad.set_key_map(settings.key_map.unwrap_or_default());
+    Ok((settings, rom_info))
+}
+
+/// Load the new contents of `file` after it changed on disk, keeping the quirks and
+/// settings it's running with, since an edited ROM no longer matches its hash in the
+/// database. The keys are released, apart from the ones being held if `keep_keys` is true
+fn reload_rom(emu: &mut Emulator, file: &str, keep_keys: bool) -> Result<(), String> {
+    emu.load_file(file)?;
+    if !keep_keys {
+        emu.keypad.release_all();
+    }
+    Ok(())
+}
+
+/// Title for the window while running `rom`, using its title and authors if known
+fn window_title(rom: &str, rom_info: Option<&RomInfo>) -> String {
+    match rom_info {
+        Some(info) if !info.authors.is_empty() => {
+            format!(
+                "Chip8-Emulator - {} by {}",
+                info.title,
+                info.authors.join(", ")
+            )
+        }
+        Some(info) => format!("Chip8-Emulator - {}", info.title),
+        None => {
+            let name = Path::new(rom).file_name().unwrap_or_default();
+            format!("Chip8-Emulator - {}", name.to_string_lossy())
+        }
+    }
+}
+
+/// Run the emulator for a fixed number of frames without any SDL subsystems
+fn run_headless(args: &Args, sources: &SettingsSources) -> Result<(), String> {
+    let file = args.filename.as_deref().unwrap_or_default();
+    info!("Running {}", file);
+    let mut emu = Emulator::new(sources.defaults.quirks(), args.seed);
+    if args.gdb.is_some() {
+        emu.set_history_limit(args.history);
+    }
+    let (settings, _) = load_rom(&mut emu, file, sources)?;
+    let mut movie = args.movie.as_deref().map(Movie::load).transpose()?;
+
+    if let Some(reference) = &args.compare_trace {
+        return compare_trace(
+            &mut emu,
+            settings.instructions_per_frame(),
+            movie,
+            reference,
+        );
+    }
+
+    let palette = settings.palette();
+    let mut wav_recorder = args.wav.as_ref().map(|_| WavRecorder::new(settings.tone()));
+    let mut recorder = match &args.record {
+        Some(file) => Some(Recorder::start(
+            file,
+            args.record_scale,
+            palette,
+            args.record_audio.then(|| settings.tone()),
+        )?),
+        None => None,
+    };
+
+    let mut debugger = args.debugger()?;
+    let mut gdb = args.gdb.map(GdbStub::listen).transpose()?;
+
+    let mut frames_run = 0;
+    while frames_run < args.frames {
+        if let Some(gdb) = gdb.as_mut() {
+            gdb.poll(&mut emu, &mut debugger);
+        }
+        if let Some(movie) = movie.as_mut() {
+            movie.apply(frames_run, &mut emu.keypad);
+        }
+        if !debugger.run_frame(&mut emu, settings.instructions_per_frame()) {
+            if debugger.is_halted() {
+                // without GDB to carry on, breakpoints, watchpoints and faults end the run
+                if gdb.is_none() {
+                    info!("Stopped after {} frames", frames_run);
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            continue;
+        }
+        frames_run += 1;
+        if let Some(recorder) = wav_recorder.as_mut() {
+            recorder.record_frame(emu.sound_timer());
+        }
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record_frame(emu.screen(), emu.sound_timer())?;
+        }
+    }
+
+    if let (Some(recorder), Some(file)) = (wav_recorder, &args.wav) {
+        recorder.save(file)?;
+        info!("Wrote audio to {}", file);
+    }
+    if let Some(recorder) = recorder {
+        let file = recorder.finish()?;
+        info!("Saved recording to {}", file);
+    }
+    if let Some(file) = &args.screenshot {
+        let files = save_screenshot(file, emu.screen(), &palette, PIXEL_SIZE)?;
+        info!("Saved screenshot to {}", files.join(", "));
+    }
+    args.save_reports(&debugger, &emu)?;
+
+    if debugger.is_halted() && debugger.stop_reason() == StopReason::Fault {
+        return Err("Stopped by a fault in the program".to_string());
+    }
     Ok(())
 }
+
+/// Run in the terminal until Ctrl-C or Escape, with the same controls as the window apart
+/// from the debug windows, ROM browser and recording hotkey
+fn run_terminal(args: &Args, sources: &SettingsSources) -> Result<(), String> {
+    let file = args.filename.as_deref().unwrap_or_default();
+    let sound_system: Box<dyn AudioBackend> = if args.no_audio {
+        Box::new(NullAudio)
+    } else {
+        Box::new(TerminalBell::new())
+    };
+    let (mut machine, rom_info) = Machine::start(args, sources, Some(file), sound_system)?;
+    let palette = machine.settings.palette();
+    let title = window_title(file, rom_info.as_ref());
+    let mut watcher = args.hot_reload.then(|| FileWatcher::new(file));
+
+    let mut terminal = Terminal::open()?;
+    let mut status = String::new();
+    let mut show_quirks = false;
+    let mut running = true;
+    while running {
+        for event in terminal.poll_events() {
+            match event {
+                TerminalEvent::Quit => running = false,
+                TerminalEvent::KeyDown(key) => match Hotkey::from_key(key) {
+                    Some(Hotkey::ToggleOverlay) => show_quirks = !show_quirks,
+                    Some(
+                        Hotkey::ToggleRecording
+                        | Hotkey::RomBrowser
+                        | Hotkey::MemoryViewer
+                        | Hotkey::Inspector
+                        | Hotkey::SpriteViewer,
+                    ) => terminal.show_message("NOT AVAILABLE IN THE TERMINAL"),
+                    Some(hotkey) => {
+                        if let Some(message) = machine.handle_hotkey(hotkey, Some(file), file) {
+                            terminal.show_message(message);
+                        }
+                    }
+                    None => machine.emu.keypad.key_down(key),
+                },
+                TerminalEvent::KeyUp(key) => match Hotkey::from_key(key) {
+                    Some(Hotkey::FastForward) => machine.controls.fast_forward = false,
+                    Some(_) => {}
+                    None => machine.emu.keypad.key_up(key),
+                },
+            }
+        }
+
+        if watcher.as_mut().is_some_and(|w| w.changed()) {
+            terminal.show_message(machine.reload(file, args.keep_keys_on_reload));
+        }
+
+        if let Some(message) = machine.run_frames(false)? {
+            terminal.show_message(message);
+        }
+
+        let mut new_status = title.clone();
+        if show_quirks {
+            new_status += &format!(" - {}", machine.emu.quirks().label());
+        }
+        match terminal.message() {
+            Some(message) => new_status += &format!(" - {}", message),
+            None if machine.controls.paused => new_status += " - PAUSED",
+            None => {}
+        }
+        if machine.emu.needs_redraw() || new_status != status {
+            status = new_status;
+            terminal.draw(machine.emu.screen(), &palette, &status)?;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    drop(terminal);
+
+    machine.finish(args)
+}
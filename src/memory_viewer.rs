@@ -0,0 +1,257 @@
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+
+use crate::debug_window::{DebugWindow, HIGHLIGHT_COLOR, TEXT_COLOR};
+use crate::emulator::{Emulator, RAM_SIZE};
+use crate::text::{draw_text, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+const TEXT_SCALE: u32 = 2;
+const MARGIN: i32 = 8;
+const CHAR_WIDTH: i32 = ((GLYPH_WIDTH + 1) * TEXT_SCALE) as i32;
+const LINE_HEIGHT: i32 = ((GLYPH_HEIGHT + 3) * TEXT_SCALE) as i32;
+const BYTES_PER_ROW: usize = 8;
+const ROWS: usize = RAM_SIZE / BYTES_PER_ROW;
+const VISIBLE_ROWS: usize = 40;
+const HEADER_LINES: i32 = 2;
+
+// Columns of each row, in characters: `200: 00 E0 A2 2A 60 0C 61 08  ..*`.a.` then the sprite
+const HEX_COLUMN: i32 = 5;
+const ASCII_COLUMN: i32 = HEX_COLUMN + BYTES_PER_ROW as i32 * 3 + 1;
+const SPRITE_COLUMN: i32 = ASCII_COLUMN + BYTES_PER_ROW as i32 + 1;
+// Each byte is one row of the sprite column, so a row of the dump is exactly one line tall
+const SPRITE_PIXEL: u32 = LINE_HEIGHT as u32 / BYTES_PER_ROW as u32;
+
+const WIDTH: u32 = (MARGIN * 2 + SPRITE_COLUMN * CHAR_WIDTH) as u32 + SPRITE_PIXEL * 8;
+const HEIGHT: u32 = (MARGIN * 2 + (HEADER_LINES + VISIBLE_ROWS as i32) * LINE_HEIGHT) as u32;
+
+/// How long bytes written by the program stay coloured, about a second at the default speed
+const RECENT_WRITE_CYCLES: u64 = 700;
+
+const PC_COLOR: Color = Color::RGB(0x20, 0x40, 0x80);
+const I_COLOR: Color = Color::RGB(0x20, 0x60, 0x30);
+const WRITTEN_COLOR: Color = Color::RGB(0xFF, 0x60, 0x60);
+
+/// Window showing all of memory as a hex dump, with the bytes as text and as sprite rows.
+/// Bytes can be edited by typing hex digits while the emulator is paused
+pub struct MemoryViewer {
+    window: DebugWindow,
+    cursor: u16,
+    top_row: usize,
+    pending_digit: Option<u8>, // first digit of a byte being typed in
+}
+
+impl MemoryViewer {
+    pub fn new() -> Self {
+        Self {
+            window: DebugWindow::new("Chip8-Emulator - Memory", WIDTH, HEIGHT),
+            cursor: 0x200,
+            top_row: 0x200 / BYTES_PER_ROW,
+            pending_digit: None,
+        }
+    }
+
+    pub fn toggle(&mut self, video: &VideoSubsystem) -> Result<(), String> {
+        self.window.toggle(video)
+    }
+
+    pub fn close(&mut self) {
+        self.window.close();
+    }
+
+    /// Tells if events for the window with `window_id` belong to the viewer
+    pub fn owns(&self, window_id: u32) -> bool {
+        self.window.owns(window_id)
+    }
+
+    /// Move the cursor with the arrow, page and home/end keys, G and I jump to the PC and
+    /// the index register. Hex digits change the byte under the cursor if `paused`. P is
+    /// left to the pause hotkey, since editing needs the emulator paused
+    pub fn handle_key(&mut self, key: Keycode, emu: &mut Emulator, paused: bool) {
+        let row = BYTES_PER_ROW as i32;
+        let page = row * VISIBLE_ROWS as i32;
+        match key {
+            Keycode::LEFT => self.move_cursor(-1),
+            Keycode::RIGHT => self.move_cursor(1),
+            Keycode::UP => self.move_cursor(-row),
+            Keycode::DOWN => self.move_cursor(row),
+            Keycode::PAGEUP => self.move_cursor(-page),
+            Keycode::PAGEDOWN => self.move_cursor(page),
+            Keycode::HOME => self.jump_to(0),
+            Keycode::END => self.jump_to(RAM_SIZE as u16 - 1),
+            Keycode::G => self.jump_to(emu.pc()),
+            Keycode::I => self.jump_to(emu.i()),
+            Keycode::ESCAPE => self.pending_digit = None,
+            _ => {
+                if let (Some(digit), true) = (hex_digit(key), paused) {
+                    self.type_digit(digit, emu);
+                }
+            }
+        }
+    }
+
+    /// Scroll the cursor by `rows` rows, for the mouse wheel
+    pub fn scroll(&mut self, rows: i32) {
+        self.move_cursor(rows * BYTES_PER_ROW as i32);
+    }
+
+    fn move_cursor(&mut self, offset: i32) {
+        let cursor = (self.cursor as i32 + offset).clamp(0, RAM_SIZE as i32 - 1);
+        self.jump_to(cursor as u16);
+    }
+
+    fn jump_to(&mut self, address: u16) {
+        self.cursor = address % RAM_SIZE as u16;
+        self.pending_digit = None;
+    }
+
+    /// The first digit typed is the high nibble, the second writes the byte and moves on
+    fn type_digit(&mut self, digit: u8, emu: &mut Emulator) {
+        match self.pending_digit.take() {
+            None => self.pending_digit = Some(digit),
+            Some(high) => {
+                emu.poke(self.cursor, high << 4 | digit);
+                self.move_cursor(1);
+            }
+        }
+    }
+
+    /// Redraw the window with the current state of `emu`, if it's open
+    pub fn draw(&mut self, emu: &Emulator, paused: bool) -> Result<(), String> {
+        let cursor_row = self.cursor as usize / BYTES_PER_ROW;
+        if cursor_row < self.top_row {
+            self.top_row = cursor_row;
+        } else if cursor_row >= self.top_row + VISIBLE_ROWS {
+            self.top_row = cursor_row + 1 - VISIBLE_ROWS;
+        }
+        let top_row = self.top_row;
+        let cursor = self.cursor;
+        let pending_digit = self.pending_digit;
+
+        let Some(canvas) = self.window.canvas_to_draw() else {
+            return Ok(());
+        };
+
+        canvas.set_draw_color(HIGHLIGHT_COLOR);
+        let header = format!(
+            "CURSOR {:03X}  PC {:03X}  I {:03X}",
+            cursor,
+            emu.pc(),
+            emu.i()
+        );
+        draw_text(canvas, &header, MARGIN, MARGIN, TEXT_SCALE)?;
+        canvas.set_draw_color(TEXT_COLOR);
+        let hint = if paused {
+            "TYPE HEX TO EDIT, G/I GO TO PC/I"
+        } else {
+            "P TO PAUSE AND EDIT, G/I GO TO PC/I"
+        };
+        draw_text(canvas, hint, MARGIN, MARGIN + LINE_HEIGHT, TEXT_SCALE)?;
+
+        for (line, row) in (top_row..ROWS.min(top_row + VISIBLE_ROWS)).enumerate() {
+            let y = MARGIN + (HEADER_LINES + line as i32) * LINE_HEIGHT;
+            let start = row * BYTES_PER_ROW;
+            let bytes = &emu.ram()[start..start + BYTES_PER_ROW];
+
+            canvas.set_draw_color(TEXT_COLOR);
+            draw_text(canvas, &format!("{:03X}:", start), MARGIN, y, TEXT_SCALE)?;
+
+            for (column, &byte) in bytes.iter().enumerate() {
+                let address = start + column;
+                let x = MARGIN + (HEX_COLUMN + column as i32 * 3) * CHAR_WIDTH;
+                let cell = Rect::new(
+                    x - 2,
+                    y - 2,
+                    (CHAR_WIDTH * 2 + 2) as u32,
+                    LINE_HEIGHT as u32,
+                );
+
+                let pc = emu.pc() as usize;
+                if address == pc || address == pc + 1 {
+                    canvas.set_draw_color(PC_COLOR);
+                    canvas.fill_rect(cell)?;
+                } else if address == emu.i() as usize {
+                    canvas.set_draw_color(I_COLOR);
+                    canvas.fill_rect(cell)?;
+                }
+                if address == cursor as usize {
+                    canvas.set_draw_color(HIGHLIGHT_COLOR);
+                    canvas.draw_rect(cell)?;
+                }
+
+                let written = emu.write_cycles()[address];
+                let recently_written = written != 0 && emu.cycles() - written < RECENT_WRITE_CYCLES;
+                canvas.set_draw_color(if recently_written {
+                    WRITTEN_COLOR
+                } else {
+                    TEXT_COLOR
+                });
+                let text = match pending_digit {
+                    Some(high) if address == cursor as usize => format!("{:X}_", high),
+                    _ => format!("{:02X}", byte),
+                };
+                draw_text(canvas, &text, x, y, TEXT_SCALE)?;
+            }
+
+            canvas.set_draw_color(TEXT_COLOR);
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| {
+                    if (0x20..0x7F).contains(&b) {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            draw_text(
+                canvas,
+                &ascii,
+                MARGIN + ASCII_COLUMN * CHAR_WIDTH,
+                y,
+                TEXT_SCALE,
+            )?;
+
+            draw_sprite_rows(canvas, bytes, MARGIN + SPRITE_COLUMN * CHAR_WIDTH, y)?;
+        }
+
+        canvas.present();
+        Ok(())
+    }
+}
+
+/// Draw each byte as a row of 8 pixels, one under the other
+fn draw_sprite_rows(
+    canvas: &mut Canvas<Window>,
+    bytes: &[u8],
+    x: i32,
+    y: i32,
+) -> Result<(), String> {
+    let mut pixels: Vec<Rect> = Vec::new();
+    for (row, byte) in bytes.iter().enumerate() {
+        for bit in 0..8 {
+            if (byte >> (7 - bit)) & 1 == 1 {
+                pixels.push(Rect::new(
+                    x + (bit * SPRITE_PIXEL) as i32,
+                    y + (row as u32 * SPRITE_PIXEL) as i32,
+                    SPRITE_PIXEL,
+                    SPRITE_PIXEL,
+                ));
+            }
+        }
+    }
+    canvas.fill_rects(&pixels)
+}
+
+/// Value of the hex digit typed with `key`, if it is one
+fn hex_digit(key: Keycode) -> Option<u8> {
+    let name = key.name();
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c.to_digit(16).map(|d| d as u8),
+        _ => None,
+    }
+}
@@ -0,0 +1,109 @@
+/// Input recorded ahead of time, to replay the same key presses on every run. Movie files
+/// have a line for each key press or release, with the frame it happens at, the CHIP8 key
+/// in hex and `down` or `up`, e.g. `120 5 down`. Lines starting with `#` are comments
+use std::fs;
+
+use crate::keypad::{Keypad, NUM_KEYS};
+
+#[derive(Debug, Clone, Copy)]
+struct KeyEvent {
+    frame: u32,
+    key: u8,
+    pressed: bool,
+}
+
+pub struct Movie {
+    events: Vec<KeyEvent>, // sorted by frame
+    next: usize,
+}
+
+impl Movie {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+        Self::parse(&contents).map_err(|e| format!("{} {}", file, e))
+    }
+
+    /// A movie from the contents of a movie file
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut events = contents
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                parse_event(line).ok_or_else(|| {
+                    format!(
+                        "line {}: expected a frame, a key and down or up, e.g. `120 5 down`",
+                        number
+                    )
+                })
+            })
+            .collect::<Result<Vec<KeyEvent>, String>>()?;
+        events.sort_by_key(|event| event.frame);
+        Ok(Self { events, next: 0 })
+    }
+
+    /// Press and release the keys for `frame`, call before running each frame in order
+    pub fn apply(&mut self, frame: u32, keypad: &mut Keypad) {
+        while let Some(event) = self.events.get(self.next).filter(|e| e.frame <= frame) {
+            keypad.set_key(event.key, event.pressed);
+            self.next += 1;
+        }
+    }
+}
+
+fn parse_event(line: &str) -> Option<KeyEvent> {
+    let mut parts = line.split_whitespace();
+    let frame = parts.next()?.parse().ok()?;
+    let key = u8::from_str_radix(parts.next()?, 16)
+        .ok()
+        .filter(|&key| (key as usize) < NUM_KEYS)?;
+    let pressed = match parts.next()? {
+        "down" => true,
+        "up" => false,
+        _ => return None,
+    };
+    parts.next().is_none().then_some(KeyEvent {
+        frame,
+        key,
+        pressed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events() {
+        let event = parse_event("120 5 down").unwrap();
+        assert_eq!((event.frame, event.key, event.pressed), (120, 5, true));
+        let event = parse_event("  7\tF  up ").unwrap();
+        assert_eq!((event.frame, event.key, event.pressed), (7, 0xF, false));
+        for line in [
+            "120 5",
+            "120 10 down",
+            "x 5 down",
+            "120 5 held",
+            "120 5 up 1",
+        ] {
+            assert!(parse_event(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parse_and_apply() {
+        let error = Movie::parse("120 5 down\nnonsense\n").err().unwrap();
+        assert!(error.starts_with("line 2: "), "{}", error);
+
+        let mut movie = Movie::parse("# hold 5\n180 5 up\n\n120 5 down\n150 a down\n").unwrap();
+        let mut keypad = Keypad::new();
+        movie.apply(119, &mut keypad);
+        assert!(!keypad.get_keys()[5]);
+        movie.apply(160, &mut keypad);
+        assert!(keypad.get_keys()[5] && keypad.get_keys()[0xA]);
+        movie.apply(180, &mut keypad);
+        assert!(!keypad.get_keys()[5] && keypad.get_keys()[0xA]);
+    }
+}
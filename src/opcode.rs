@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 
 pub struct Opcode {
     pub category: u8, // The first 4 bits (bit 1-4). Tells you what kind of instruction it is
@@ -34,3 +34,48 @@ impl Opcode {
         }
     }
 }
+
+/// Writes the instruction in assembly, e.g. `LD V1, 0x20` or `DRW V0, V1, 5`,
+/// instructions that don't exist are written as data with `DW`
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (x, y, n, nn, nnn) = (self.x, self.y, self.n, self.nn, self.nnn);
+        match (self.category, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => write!(f, "CLS"),
+            (0x0, 0x0, 0xE, 0xE) => write!(f, "RET"),
+            (0x1, ..) => write!(f, "JP {:#05X}", nnn),
+            (0x2, ..) => write!(f, "CALL {:#05X}", nnn),
+            (0x3, ..) => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            (0x4, ..) => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            (0x5, _, _, 0x0) => write!(f, "SE V{:X}, V{:X}", x, y),
+            (0x6, ..) => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            (0x7, ..) => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            (0x8, _, _, 0x0) => write!(f, "LD V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x1) => write!(f, "OR V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x2) => write!(f, "AND V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x3) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x4) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x5) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x6) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0x7) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            (0x8, _, _, 0xE) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            (0x9, _, _, 0x0) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            (0xA, ..) => write!(f, "LD I, {:#05X}", nnn),
+            (0xB, ..) => write!(f, "JP V0, {:#05X}", nnn),
+            (0xC, ..) => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            (0xD, ..) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            (0xE, _, 0x9, 0xE) => write!(f, "SKP V{:X}", x),
+            (0xE, _, 0xA, 0x1) => write!(f, "SKNP V{:X}", x),
+            (0xF, _, 0x0, 0x7) => write!(f, "LD V{:X}, DT", x),
+            (0xF, _, 0x0, 0xA) => write!(f, "LD V{:X}, K", x),
+            (0xF, _, 0x1, 0x5) => write!(f, "LD DT, V{:X}", x),
+            (0xF, _, 0x1, 0x8) => write!(f, "LD ST, V{:X}", x),
+            (0xF, _, 0x1, 0xE) => write!(f, "ADD I, V{:X}", x),
+            (0xF, _, 0x2, 0x9) => write!(f, "LD F, V{:X}", x),
+            (0xF, _, 0x3, 0x3) => write!(f, "LD B, V{:X}", x),
+            (0xF, _, 0x5, 0x5) => write!(f, "LD [I], V{:X}", x),
+            (0xF, _, 0x6, 0x5) => write!(f, "LD V{:X}, [I]", x),
+            _ => write!(f, "DW {:#06X}", (self.category as u16) << 12 | nnn),
+        }
+    }
+}
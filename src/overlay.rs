@@ -0,0 +1,134 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use std::time::{Duration, Instant};
+
+use crate::text::{draw_text, text_width, GLYPH_HEIGHT};
+
+const TEXT_SCALE: u32 = 3;
+const MARGIN: i32 = 8;
+const LINE_HEIGHT: i32 = ((GLYPH_HEIGHT + 2) * TEXT_SCALE) as i32;
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const REFRESH_INTERVAL: Duration = Duration::from_millis(1000 / 60);
+const TEXT_COLOR: Color = Color::RGB(0xFF, 0xD7, 0x00);
+const BACKGROUND_COLOR: Color = Color::RGBA(0x00, 0x00, 0x00, 0xB0);
+
+/// Text drawn on top of the emulator screen: performance stats, the quirks in use,
+/// short lived messages and a banner when paused
+pub struct Overlay {
+    pub show_stats: bool,
+    message: Option<(String, Instant)>, // message and when it was shown
+    frames: u32,                        // frames drawn since the stats were last updated
+    last_cycles: u64,                   // emulator cycles when the stats were last updated
+    last_stats_update: Instant,
+    last_refresh: Instant,
+    fps: u32,
+    ips: u64,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            show_stats: false,
+            message: None,
+            frames: 0,
+            last_cycles: 0,
+            last_stats_update: Instant::now(),
+            last_refresh: Instant::now(),
+            fps: 0,
+            ips: 0,
+        }
+    }
+
+    /// Show `message` for a couple of seconds
+    pub fn show_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    /// Tells if the overlay has something on screen that changes over time, so the window
+    /// should be redrawn even if the emulator screen hasn't changed. Limited to 60 times per second
+    pub fn needs_refresh(&mut self, paused: bool) -> bool {
+        let showing = self.show_stats || paused || self.message.is_some();
+        if showing && self.last_refresh.elapsed() >= REFRESH_INTERVAL {
+            self.last_refresh = Instant::now();
+            return true;
+        }
+        false
+    }
+
+    /// Count a frame being drawn, updating the frames and instructions per second once a second
+    pub fn count_frame(&mut self, cycles: u64) {
+        self.frames += 1;
+        let elapsed = self.last_stats_update.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = (self.frames as f64 / elapsed.as_secs_f64()).round() as u32;
+            // cycles go down when stepping back, count that as not running rather than wrapping
+            let executed = cycles.saturating_sub(self.last_cycles);
+            self.ips = (executed as f64 / elapsed.as_secs_f64()).round() as u64;
+            self.frames = 0;
+            self.last_cycles = cycles;
+            self.last_stats_update = Instant::now();
+        }
+    }
+
+    /// Draw the overlay on top of whatever is already on the canvas
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        quirks_label: &str,
+        paused: bool,
+    ) -> Result<(), String> {
+        if let Some((_, shown_at)) = &self.message {
+            if shown_at.elapsed() >= MESSAGE_DURATION {
+                self.message = None;
+            }
+        }
+
+        let mut lines: Vec<String> = Vec::new();
+        if self.show_stats {
+            lines.push(format!("FPS {}", self.fps));
+            lines.push(format!("IPS {}", self.ips));
+            lines.push(format!("QUIRKS {}", quirks_label));
+        }
+        if let Some((message, _)) = &self.message {
+            lines.push(message.clone());
+        }
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        for (i, line) in lines.iter().enumerate() {
+            draw_label(canvas, line, MARGIN, MARGIN + i as i32 * LINE_HEIGHT)?;
+        }
+
+        if paused {
+            let banner = "PAUSED";
+            let (width, height) = canvas.output_size()?;
+            let x = (width - text_width(banner, TEXT_SCALE * 2)) as i32 / 2;
+            let y = (height - GLYPH_HEIGHT * TEXT_SCALE * 2) as i32 / 2;
+            canvas.set_draw_color(BACKGROUND_COLOR);
+            canvas.fill_rect(Rect::new(
+                x - MARGIN,
+                y - MARGIN,
+                text_width(banner, TEXT_SCALE * 2) + MARGIN as u32 * 2,
+                GLYPH_HEIGHT * TEXT_SCALE * 2 + MARGIN as u32 * 2,
+            ))?;
+            canvas.set_draw_color(TEXT_COLOR);
+            draw_text(canvas, banner, x, y, TEXT_SCALE * 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// Draw a line of text on a dark box so it can be read over any screen contents
+fn draw_label(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32) -> Result<(), String> {
+    let padding = TEXT_SCALE as i32;
+    canvas.set_draw_color(BACKGROUND_COLOR);
+    canvas.fill_rect(Rect::new(
+        x - padding,
+        y - padding,
+        text_width(text, TEXT_SCALE) + padding as u32 * 2,
+        GLYPH_HEIGHT * TEXT_SCALE + padding as u32 * 2,
+    ))?;
+    canvas.set_draw_color(TEXT_COLOR);
+    draw_text(canvas, text, x, y, TEXT_SCALE)
+}
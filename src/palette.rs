@@ -0,0 +1,81 @@
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+use crate::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Colours used to draw the screen, as RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub background: [u8; 3], // colour of pixels that are off
+    pub foreground: [u8; 3], // colour of pixels that are on
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: [0x00, 0x00, 0x00],
+            foreground: [0xFF, 0xFF, 0xFF],
+        }
+    }
+}
+
+impl Palette {
+    /// Colour to draw a pixel with, depending on whether it's on
+    pub fn color(&self, on: bool) -> [u8; 3] {
+        if on {
+            self.foreground
+        } else {
+            self.background
+        }
+    }
+
+    /// Render the screen to RGB bytes, with every emulator pixel becoming a
+    /// `scale` by `scale` block
+    pub fn render(&self, screen: &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT], scale: usize) -> Vec<u8> {
+        (0..SCREEN_HEIGHT * scale)
+            .flat_map(|y| (0..SCREEN_WIDTH * scale).map(move |x| (y, x)))
+            .flat_map(|(y, x)| self.color(screen[y / scale][x / scale]))
+            .collect()
+    }
+}
+
+/// Parse a colour written as hex, e.g. `#FF8800` or `ff8800`
+pub fn parse_color(color: &str) -> Result<[u8; 3], String> {
+    let hex = color.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("{} is not a colour like #FF8800", color));
+    }
+    let mut rgb = [0; 3];
+    for (i, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("{} is not a colour like #FF8800", color))?;
+    }
+    Ok(rgb)
+}
+
+/// Write a colour as hex, e.g. `#FF8800`
+pub fn format_color(color: [u8; 3]) -> String {
+    format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2])
+}
+
+/// Serde helpers to store optional colours as hex in settings files
+pub mod hex_color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        color: &Option<[u8; 3]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => serializer.serialize_some(&format_color(*color)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<[u8; 3]>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|color| parse_color(&color).map_err(de::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+
+use crate::emulator::{Emulator, RAM_SIZE, START_ADDR};
+use crate::opcode::Opcode;
+
+const HOT_SPOTS: usize = 20; // addresses listed in the summary at the top of the report
+const BAR_WIDTH: u64 = 40;
+
+/// What each opcode category does, by the first hex digit of the opcode
+const CATEGORY_NAMES: [&str; 16] = [
+    "0NNN CLS/RET",
+    "1NNN JP",
+    "2NNN CALL",
+    "3XNN SE",
+    "4XNN SNE",
+    "5XY0 SE",
+    "6XNN LD",
+    "7XNN ADD",
+    "8XYN ALU",
+    "9XY0 SNE",
+    "ANNN LD I",
+    "BNNN JP V0",
+    "CXNN RND",
+    "DXYN DRW",
+    "EXNN SKP/SKNP",
+    "FXNN TIMERS/MEMORY",
+];
+
+/// Counts how many times each address and each opcode category is executed
+pub struct Profiler {
+    address_counts: Vec<u64>,
+    category_counts: [u64; 16],
+    hottest: u64, // count of the most executed address
+    instructions: u64,
+    frames: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            address_counts: vec![0; RAM_SIZE],
+            category_counts: [0; 16],
+            hottest: 0,
+            instructions: 0,
+            frames: 0,
+        }
+    }
+
+    /// Count the instruction `opcode` at `pc` having run, and whether it finished a frame
+    pub fn record(&mut self, pc: u16, opcode: u16, finished_frame: bool) {
+        let count = &mut self.address_counts[pc as usize % RAM_SIZE];
+        *count += 1;
+        self.hottest = self.hottest.max(*count);
+        self.category_counts[(opcode >> 12) as usize] += 1;
+        self.instructions += 1;
+        if finished_frame {
+            self.frames += 1;
+        }
+    }
+
+    /// Write the report to `file`: the time spent in each opcode category, the hottest
+    /// addresses, and the disassembly of the program with the count for every instruction
+    pub fn save(&self, file: &str, emu: &Emulator) -> Result<(), String> {
+        fs::write(file, self.report(emu)).map_err(|e| format!("Could not write {}: {}", file, e))
+    }
+
+    fn report(&self, emu: &Emulator) -> String {
+        let mut report = format!(
+            "{} instructions over {} frames, {:.1} per frame\n",
+            self.instructions,
+            self.frames,
+            self.per_frame(self.instructions)
+        );
+
+        report += "\nBY CATEGORY\n";
+        let mut categories: Vec<(usize, u64)> = self
+            .category_counts
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        categories.sort_by_key(|(category, count)| (u64::MAX - count, *category));
+        for (category, count) in categories {
+            report += &format!(
+                "{:<20} {:>12} {:>6.2}% {:>8.1}/frame\n",
+                CATEGORY_NAMES[category],
+                count,
+                self.percent(count),
+                self.per_frame(count)
+            );
+        }
+
+        report += "\nHOT SPOTS\n";
+        let mut hot_spots: Vec<(usize, u64)> = self
+            .address_counts
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        hot_spots.sort_by_key(|(address, count)| (u64::MAX - count, *address));
+        for (address, _) in hot_spots.iter().take(HOT_SPOTS) {
+            report += &self.line(*address as u16, emu);
+        }
+
+        report += "\nDISASSEMBLY\n";
+        for address in self.listed_addresses(emu) {
+            report += &self.line(address, emu);
+        }
+        report
+    }
+
+    /// The program two bytes at a time, along with any other address that was executed,
+    /// which catches code that isn't aligned to the start of the program
+    fn listed_addresses(&self, emu: &Emulator) -> Vec<u16> {
+        let program_start = START_ADDR as usize;
+        let program_end = program_start + emu.rom().len();
+        let mut addresses: Vec<u16> = (program_start..program_end)
+            .step_by(2)
+            .map(|address| address as u16)
+            .chain(
+                self.address_counts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(address, _)| address as u16),
+            )
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+
+    /// A line of the report for `address`, with a bar as long as its share of the hottest
+    /// address's count, to spot the hot loops at a glance
+    fn line(&self, address: u16, emu: &Emulator) -> String {
+        let count = self.address_counts[address as usize];
+        let hottest = self.hottest.max(1);
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(hottest) as usize);
+        let instruction = emu.instruction_at(address);
+        format!(
+            "{:03X} {:04X} {:<18} {:>12} {:>6.2}% {:>8.1}/frame {}\n",
+            address,
+            instruction,
+            Opcode::decode(instruction).to_string(),
+            count,
+            self.percent(count),
+            self.per_frame(count),
+            bar
+        )
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.instructions.max(1) as f64
+    }
+
+    fn per_frame(&self, count: u64) -> f64 {
+        count as f64 / self.frames.max(1) as f64
+    }
+}
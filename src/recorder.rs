@@ -0,0 +1,223 @@
+/// Records the screen to an animated GIF at 50 frames per second, or a Y4M video stream at
+/// 60 frames per second. Y4M format reference: https://wiki.multimedia.cx/index.php/YUV4MPEG2
+use gif::{Encoder, Frame, Repeat};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::palette::Palette;
+use crate::sound::Tone;
+use crate::wav::WavRecorder;
+
+const FRAME_RATE: u32 = 60;
+
+/// Delay between GIF frames in hundredths of a second. Viewers slow delays of 1 down to
+/// about 10, so GIFs are recorded at 50 frames per second, dropping frames to keep up
+const GIF_DELAY: u32 = 2;
+
+/// Largest scale for recordings, already 2048 pixels wide and well past what's useful
+pub const MAX_SCALE: usize = 32;
+
+enum VideoEncoder {
+    Gif(Encoder<Box<dyn Write>>),
+    Y4m(Box<dyn Write>),
+}
+
+pub struct Recorder {
+    file: String,
+    encoder: VideoEncoder,
+    scale: usize,
+    palette: Palette,
+    frames: u32,
+    gif_frames: u32, // frames written to a GIF, fewer than the frames recorded
+    audio: Option<WavRecorder>,
+}
+
+impl Recorder {
+    /// Start recording to `file`, the format is picked from its extension, either `.gif` or
+    /// `.y4m`. Every emulator pixel becomes a `scale` by `scale` block in the recording.
+    /// If `audio` is set the beeper is also recorded with that tone, to a WAV file next
+    /// to the video
+    pub fn start(
+        file: &str,
+        scale: usize,
+        palette: Palette,
+        audio: Option<Tone>,
+    ) -> Result<Self, String> {
+        check_scale(scale)?;
+        let writer = BufWriter::new(File::create(file).map_err(|e| e.to_string())?);
+        Self::to_writer(file, Box::new(writer), scale, palette, audio)
+    }
+
+    /// Start recording in the format of `file` to `writer` instead of to the file itself
+    fn to_writer(
+        file: &str,
+        writer: Box<dyn Write>,
+        scale: usize,
+        palette: Palette,
+        audio: Option<Tone>,
+    ) -> Result<Self, String> {
+        let width = SCREEN_WIDTH * scale;
+        let height = SCREEN_HEIGHT * scale;
+        let encoder = match Path::new(file).extension().and_then(|e| e.to_str()) {
+            Some("gif") => {
+                let colors = [palette.background, palette.foreground].concat();
+                let mut encoder = Encoder::new(writer, width as u16, height as u16, &colors)
+                    .map_err(|e| e.to_string())?;
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| e.to_string())?;
+                VideoEncoder::Gif(encoder)
+            }
+            Some("y4m") => {
+                let mut writer = writer;
+                // C444 so pixels don't get blurred by chroma subsampling
+                writeln!(
+                    writer,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    width, height, FRAME_RATE
+                )
+                .map_err(|e| e.to_string())?;
+                VideoEncoder::Y4m(writer)
+            }
+            _ => return Err(format!("Can't record to {}, use a .gif or .y4m file", file)),
+        };
+
+        Ok(Self {
+            file: file.to_string(),
+            encoder,
+            scale,
+            palette,
+            frames: 0,
+            gif_frames: 0,
+            audio: audio.map(WavRecorder::new),
+        })
+    }
+
+    /// Record a frame, called once for every 60Hz frame the emulator runs
+    pub fn record_frame(
+        &mut self,
+        screen: &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        sound_timer: &u8,
+    ) -> Result<(), String> {
+        let width = SCREEN_WIDTH * self.scale;
+        let height = SCREEN_HEIGHT * self.scale;
+        // Scaled up screen, 1 for pixels that are on, 0 otherwise, indexes into the palette
+        let pixels: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (y, x)))
+            .map(|(y, x)| screen[y / self.scale][x / self.scale] as u8)
+            .collect();
+
+        match &mut self.encoder {
+            VideoEncoder::Gif(encoder) => {
+                // only the frames that start a new GIF frame are kept, 5 in every 6
+                let elapsed = self.frames * 100 / FRAME_RATE;
+                if elapsed >= self.gif_frames * GIF_DELAY {
+                    let mut frame =
+                        Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+                    frame.delay = GIF_DELAY as u16;
+                    encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+                    self.gif_frames += 1;
+                }
+            }
+            VideoEncoder::Y4m(writer) => {
+                let colors = [
+                    rgb_to_yuv(self.palette.background),
+                    rgb_to_yuv(self.palette.foreground),
+                ];
+                // Planar, all the Y values, then all the U values, then all the V values
+                let planes: Vec<u8> = (0..3)
+                    .flat_map(|plane| pixels.iter().map(move |&p| colors[p as usize][plane]))
+                    .collect();
+                writer.write_all(b"FRAME\n").map_err(|e| e.to_string())?;
+                writer.write_all(&planes).map_err(|e| e.to_string())?;
+            }
+        }
+
+        if let Some(audio) = self.audio.as_mut() {
+            audio.record_frame(sound_timer);
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Stop recording, flushing the video and writing the audio if it was recorded.
+    /// Returns the name of the video file
+    pub fn finish(self) -> Result<String, String> {
+        match self.encoder {
+            VideoEncoder::Gif(encoder) => {
+                encoder
+                    .into_inner()
+                    .map_err(|e| e.to_string())?
+                    .flush()
+                    .map_err(|e| e.to_string())?;
+            }
+            VideoEncoder::Y4m(mut writer) => writer.flush().map_err(|e| e.to_string())?,
+        }
+
+        if let Some(audio) = self.audio {
+            audio.save(
+                &Path::new(&self.file)
+                    .with_extension("wav")
+                    .to_string_lossy(),
+            )?;
+        }
+        Ok(self.file)
+    }
+}
+
+/// Parse the size of each emulator pixel in recordings, from 1 to `MAX_SCALE`
+pub fn parse_scale(scale: &str) -> Result<usize, String> {
+    let scale = scale
+        .parse()
+        .map_err(|_| format!("{} is not a number", scale))?;
+    check_scale(scale)
+}
+
+fn check_scale(scale: usize) -> Result<usize, String> {
+    if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(format!(
+            "The recording scale should be from 1 to {}, not {}",
+            MAX_SCALE, scale
+        ));
+    }
+    Ok(scale)
+}
+
+/// Convert an RGB color to limited range BT.601 YUV, the colour space Y4M players expect
+fn rgb_to_yuv(color: [u8; 3]) -> [u8; 3] {
+    let [r, g, b] = color.map(|c| c as f32);
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn gifs_are_recorded_at_50_frames_per_second() {
+        let writer = Box::new(io::sink());
+        let mut recorder =
+            Recorder::to_writer("clip.gif", writer, 1, Palette::default(), None).unwrap();
+        let screen = [[false; SCREEN_WIDTH]; SCREEN_HEIGHT];
+        for _ in 0..60 {
+            recorder.record_frame(&screen, &0).unwrap();
+        }
+        assert_eq!(recorder.gif_frames, 50);
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(parse_scale("4"), Ok(4));
+        assert_eq!(parse_scale("32"), Ok(MAX_SCALE));
+        assert!(parse_scale("0").is_err());
+        assert!(parse_scale("33").is_err());
+        assert!(parse_scale("big").is_err());
+        assert!(Recorder::start("unused.gif", 0, Palette::default(), None).is_err());
+    }
+}
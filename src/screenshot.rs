@@ -0,0 +1,65 @@
+use png::{BitDepth, ColorType, Encoder};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::palette::Palette;
+
+/// Save the screen as a PNG at 1x to `file`, and at `scale` next to it with the scale in
+/// the name, e.g. `shot.png` and `shot-16x.png`. Returns the names of the files written
+pub fn save_screenshot(
+    file: &str,
+    screen: &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    palette: &Palette,
+    scale: usize,
+) -> Result<Vec<String>, String> {
+    let path = Path::new(file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let scaled_file = path
+        .with_file_name(format!("{}-{}x.png", stem, scale))
+        .to_string_lossy()
+        .into_owned();
+
+    save_png(file, screen, palette, 1)?;
+    save_png(&scaled_file, screen, palette, scale)?;
+    Ok(vec![file.to_string(), scaled_file])
+}
+
+/// Save the screen as a PNG, with every emulator pixel becoming a `scale` by `scale` block
+pub fn save_png(
+    file: &str,
+    screen: &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+    palette: &Palette,
+    scale: usize,
+) -> Result<(), String> {
+    let writer = BufWriter::new(File::create(file).map_err(|e| e.to_string())?);
+    let mut encoder = Encoder::new(
+        writer,
+        (SCREEN_WIDTH * scale) as u32,
+        (SCREEN_HEIGHT * scale) as u32,
+    );
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&palette.render(screen, scale))
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+/// Name for a file next to the ROM, made unique with the current time,
+/// e.g. `games/pong.ch8` becomes `games/pong-1718000000.png`
+pub fn timestamped_file(rom: &str, extension: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let rom = Path::new(rom);
+    let stem = rom.file_stem().unwrap_or_default().to_string_lossy();
+    rom.with_file_name(format!("{}-{}.{}", stem, timestamp, extension))
+        .to_string_lossy()
+        .into_owned()
+}
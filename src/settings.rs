@@ -0,0 +1,161 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+
+use crate::database::{RomDatabase, RomInfo};
+use crate::emulator::Quirks;
+use crate::keypad::KeyMap;
+use crate::palette::{hex_color, Palette};
+use crate::sound::Tone;
+
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 11;
+
+/// Settings that can differ per ROM. Each source only sets what it knows about,
+/// and they're layered on top of each other with `or`.
+/// Also the format of the settings file kept next to a ROM
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RomSettings {
+    pub shift_with_y: Option<bool>,
+    pub jump_with_x: Option<bool>,
+    pub modify_i_in_load_and_store: Option<bool>,
+    pub instructions_per_frame: Option<u32>,
+    #[serde(with = "hex_color")]
+    pub foreground: Option<[u8; 3]>,
+    #[serde(with = "hex_color")]
+    pub background: Option<[u8; 3]>,
+    pub frequency: Option<f32>,
+    pub volume: Option<f32>,
+    #[serde(rename = "keys")]
+    pub key_map: Option<KeyMap>,
+}
+
+impl RomSettings {
+    /// These settings, with any that aren't set taken from `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            shift_with_y: self.shift_with_y.or(other.shift_with_y),
+            jump_with_x: self.jump_with_x.or(other.jump_with_x),
+            modify_i_in_load_and_store: self
+                .modify_i_in_load_and_store
+                .or(other.modify_i_in_load_and_store),
+            instructions_per_frame: self.instructions_per_frame.or(other.instructions_per_frame),
+            foreground: self.foreground.or(other.foreground),
+            background: self.background.or(other.background),
+            frequency: self.frequency.or(other.frequency),
+            volume: self.volume.or(other.volume),
+            key_map: self.key_map.or(other.key_map),
+        }
+    }
+
+    /// Load the settings file next to `rom_file`, None if there isn't one
+    pub fn load_sidecar(rom_file: &str) -> Result<Option<Self>, String> {
+        let file = sidecar_file(rom_file);
+        match fs::read_to_string(&file) {
+            Ok(contents) => toml::from_str(&contents)
+                .map(Some)
+                .map_err(|e| format!("Could not parse {}: {}", file, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Could not read {}: {}", file, e)),
+        }
+    }
+
+    /// Save these settings to the settings file next to `rom_file`, returns the file's name
+    pub fn save_sidecar(&self, rom_file: &str) -> Result<String, String> {
+        let file = sidecar_file(rom_file);
+        let contents = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&file, contents).map_err(|e| format!("Could not write {}: {}", file, e))?;
+        Ok(file)
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            use_y_on_shift: self.shift_with_y.unwrap_or_default(),
+            use_x_on_jump: self.jump_with_x.unwrap_or_default(),
+            modify_i_on_load_and_store: self.modify_i_in_load_and_store.unwrap_or_default(),
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        let default = Palette::default();
+        Palette {
+            background: self.background.unwrap_or(default.background),
+            foreground: self.foreground.unwrap_or(default.foreground),
+        }
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+            .unwrap_or(DEFAULT_INSTRUCTIONS_PER_FRAME)
+    }
+
+    pub fn tone(&self) -> Tone {
+        let default = Tone::default();
+        Tone {
+            frequency: self.frequency.unwrap_or(default.frequency),
+            volume: self.volume.unwrap_or(default.volume),
+        }
+    }
+}
+
+/// Name of the settings file for `rom_file`, e.g. `pong.ch8.toml` for `pong.ch8`
+pub fn sidecar_file(rom_file: &str) -> String {
+    format!("{}.toml", rom_file)
+}
+
+impl From<&RomInfo> for RomSettings {
+    fn from(rom_info: &RomInfo) -> Self {
+        Self {
+            shift_with_y: Some(rom_info.quirks.use_y_on_shift),
+            jump_with_x: Some(rom_info.quirks.use_x_on_jump),
+            modify_i_in_load_and_store: Some(rom_info.quirks.modify_i_on_load_and_store),
+            instructions_per_frame: Some(rom_info.instructions_per_frame),
+            foreground: rom_info.palette.map(|p| p.foreground),
+            background: rom_info.palette.map(|p| p.background),
+            frequency: None,
+            volume: None,
+            key_map: rom_info.key_map,
+        }
+    }
+}
+
+/// Where the settings for a ROM come from, in order of priority: flags passed on the
+/// command line, the settings file next to the ROM, the ROM database, then the defaults
+/// of the command line flags
+pub struct SettingsSources {
+    pub command_line: RomSettings,
+    pub database: Option<RomDatabase>,
+    pub defaults: RomSettings,
+}
+
+impl SettingsSources {
+    /// Work out the settings for `rom` loaded from `rom_file`, also returns what the
+    /// database knows about it
+    pub fn settings_for(&self, rom_file: &str, rom: &[u8]) -> (RomSettings, Option<RomInfo>) {
+        let rom_info = self.database.as_ref().and_then(|db| db.lookup(rom));
+        if let Some(rom_info) = &rom_info {
+            info!(
+                "Found {} in the ROM database, running it as {}",
+                rom_info.title, rom_info.platform
+            );
+        }
+
+        let sidecar = RomSettings::load_sidecar(rom_file).unwrap_or_else(|e| {
+            warn!("Ignoring ROM settings file: {}", e);
+            None
+        });
+        if sidecar.is_some() {
+            info!("Using settings from {}", sidecar_file(rom_file));
+        }
+
+        let database = rom_info.as_ref().map(RomSettings::from).unwrap_or_default();
+        let settings = self
+            .command_line
+            .clone()
+            .or(sidecar.unwrap_or_default())
+            .or(database)
+            .or(self.defaults.clone());
+        (settings, rom_info)
+    }
+}
@@ -1,63 +1,149 @@
 /// Largely directly from docs of SDL2 bindings for Rust, I don't really understand a lot of it
 /// https://docs.rs/sdl2/latest/sdl2/audio/index.html
+#[cfg(feature = "sdl")]
+use log::warn;
+#[cfg(feature = "sdl")]
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+#[cfg(feature = "sdl")]
 use sdl2::Sdl;
 
-struct SquareWave {
+pub const SAMPLE_RATE: i32 = 44100;
+pub const DEFAULT_FREQUENCY: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.10;
+
+/// Pitch and loudness of the beep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub frequency: f32, // in Hz
+    pub volume: f32,    // from 0 to 1
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            frequency: DEFAULT_FREQUENCY,
+            volume: DEFAULT_VOLUME,
+        }
+    }
+}
+
+pub struct SquareWave {
+    sample_rate: i32,
     phase_inc: f32,
     phase: f32,
     volume: f32,
 }
 
+impl SquareWave {
+    pub fn new(sample_rate: i32, tone: Tone) -> Self {
+        let mut wave = Self {
+            sample_rate,
+            phase_inc: 0.0,
+            phase: 0.0,
+            volume: 0.0,
+        };
+        wave.set_tone(tone);
+        wave
+    }
+
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.phase_inc = tone.frequency / self.sample_rate as f32;
+        self.volume = tone.volume.clamp(0.0, 1.0);
+    }
+
+    /// Generate the next sample of the square wave
+    pub fn next_sample(&mut self) -> f32 {
+        let sample = if self.phase <= 0.5 {
+            self.volume
+        } else {
+            -self.volume
+        };
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+        sample
+    }
+}
+
+#[cfg(feature = "sdl")]
 impl AudioCallback for SquareWave {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
         for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            *x = self.next_sample();
         }
     }
 }
 
+/// Something that can play the CHIP8 beeper
+pub trait AudioBackend {
+    /// Resume beeping if sound timer greater than 0, pause otherwise
+    fn handle_sound_timer(&self, timer: &u8);
+
+    /// Change the pitch and loudness of the beep
+    fn set_tone(&mut self, tone: Tone);
+}
+
+/// Open the SDL audio device, or the silent backend if `no_audio` is set. Falls back to
+/// the silent backend with a warning if the device can't be opened, e.g. on machines
+/// without a sound card
+#[cfg(feature = "sdl")]
+pub fn open_audio(sdl_context: &Sdl, no_audio: bool) -> Box<dyn AudioBackend> {
+    if no_audio {
+        return Box::new(NullAudio);
+    }
+    match SoundSystem::new(sdl_context) {
+        Ok(sound_system) => Box::new(sound_system),
+        Err(e) => {
+            warn!("Could not open audio device, running without sound: {}", e);
+            Box::new(NullAudio)
+        }
+    }
+}
+
+/// Backend that never makes a sound
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn handle_sound_timer(&self, _timer: &u8) {}
+
+    fn set_tone(&mut self, _tone: Tone) {}
+}
+
+#[cfg(feature = "sdl")]
 pub struct SoundSystem {
     device: AudioDevice<SquareWave>,
 }
 
+#[cfg(feature = "sdl")]
 impl SoundSystem {
-    pub fn new(sdl_context: Sdl) -> Self {
-        let audio = sdl_context.audio().unwrap();
+    pub fn new(sdl_context: &Sdl) -> Result<Self, String> {
+        let audio = sdl_context.audio()?;
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
+            freq: Some(SAMPLE_RATE),
             channels: Some(1), // mono
             samples: None,     // default sample size
         };
 
-        Self {
-            device: audio
-                .open_playback(None, &desired_spec, |spec| {
-                    // initialize the audio callback
-                    SquareWave {
-                        phase_inc: 440.0 / spec.freq as f32,
-                        phase: 0.0,
-                        volume: 0.10,
-                    }
-                })
-                .unwrap(),
-        }
+        Ok(Self {
+            device: audio.open_playback(None, &desired_spec, |spec| {
+                // initialize the audio callback
+                SquareWave::new(spec.freq, Tone::default())
+            })?,
+        })
     }
+}
 
-    /// Resume beeping if sound timer greater than 0, pause otherwise
-    pub fn handle_sound_timer(&self, timer: &u8) {
+#[cfg(feature = "sdl")]
+impl AudioBackend for SoundSystem {
+    fn handle_sound_timer(&self, timer: &u8) {
         if *timer > 0 {
             self.device.resume();
         } else {
             self.device.pause();
         }
     }
+
+    fn set_tone(&mut self, tone: Tone) {
+        self.device.lock().set_tone(tone);
+    }
 }
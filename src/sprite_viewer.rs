@@ -0,0 +1,277 @@
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+
+use crate::debug_window::{DebugWindow, HIGHLIGHT_COLOR, TEXT_COLOR};
+use crate::emulator::{Emulator, FONT_HEIGHT, FONT_SET_SIZE, RAM_SIZE};
+use crate::text::{draw_text, GLYPH_HEIGHT};
+
+const TEXT_SCALE: u32 = 2;
+const MARGIN: i32 = 8;
+const LINE_HEIGHT: i32 = ((GLYPH_HEIGHT + 3) * TEXT_SCALE) as i32;
+
+const PREVIEW_PIXEL: u32 = 12;
+const PREVIEW_SIZE: u32 = 16 * PREVIEW_PIXEL; // big enough for a 16x16 sprite
+const THUMBNAIL_PIXEL: u32 = 3;
+const COLUMNS: usize = 8;
+const CELL_WIDTH: i32 = 56;
+const CELL_HEIGHT: i32 = LINE_HEIGHT + 16 * THUMBNAIL_PIXEL as i32 + 8;
+const NEXT_ROWS: usize = 2; // rows of the sprites following the one shown in the preview
+const FONT_ROWS: usize = 16 / COLUMNS;
+const PAGE_SPRITES: i32 = 16; // how many sprites Page Up and Page Down move
+
+const PREVIEW_TOP: i32 = MARGIN + LINE_HEIGHT * 3 + 4;
+const NEXT_TOP: i32 = PREVIEW_TOP + PREVIEW_SIZE as i32 + 8;
+const FONT_TOP: i32 = NEXT_TOP + LINE_HEIGHT + NEXT_ROWS as i32 * CELL_HEIGHT;
+
+const WIDTH: u32 = (MARGIN * 2 + COLUMNS as i32 * CELL_WIDTH) as u32;
+const HEIGHT: u32 = (FONT_TOP + LINE_HEIGHT + FONT_ROWS as i32 * CELL_HEIGHT + MARGIN) as u32;
+
+const OFF_COLOR: Color = Color::RGB(0x28, 0x28, 0x38);
+
+/// Shapes sprites can be decoded as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpriteSize {
+    Narrow(u8), // 8 pixels wide, 1 to 15 rows of one byte each, as drawn by DXYN
+    Wide,       // 16x16, rows of two bytes, as drawn by DXY0 on the SUPER-CHIP
+}
+
+impl SpriteSize {
+    fn width(self) -> u32 {
+        match self {
+            Self::Narrow(_) => 8,
+            Self::Wide => 16,
+        }
+    }
+
+    fn height(self) -> u32 {
+        match self {
+            Self::Narrow(height) => height as u32,
+            Self::Wide => 16,
+        }
+    }
+
+    /// Number of bytes one sprite takes up in memory
+    fn bytes(self) -> u16 {
+        (self.width() / 8 * self.height()) as u16
+    }
+
+    fn label(self) -> String {
+        format!("{}X{}", self.width(), self.height())
+    }
+}
+
+/// Window decoding memory as sprites, following the index register or scrubbing
+/// through memory by hand, along with the glyphs of the font
+pub struct SpriteViewer {
+    window: DebugWindow,
+    address: u16,
+    follow_i: bool,
+    size: SpriteSize,
+}
+
+impl SpriteViewer {
+    pub fn new() -> Self {
+        Self {
+            window: DebugWindow::new("Chip8-Emulator - Sprites", WIDTH, HEIGHT),
+            address: 0,
+            follow_i: true,
+            size: SpriteSize::Narrow(FONT_HEIGHT as u8),
+        }
+    }
+
+    pub fn toggle(&mut self, video: &VideoSubsystem) -> Result<(), String> {
+        self.window.toggle(video)
+    }
+
+    pub fn close(&mut self) {
+        self.window.close();
+    }
+
+    /// Tells if events for the window with `window_id` belong to the viewer
+    pub fn owns(&self, window_id: u32) -> bool {
+        self.window.owns(window_id)
+    }
+
+    /// The arrow and page keys scrub through memory, I goes back to following the
+    /// index register, S switches between 8xN and 16x16 sprites and +/- change N
+    pub fn handle_key(&mut self, key: Keycode, emu: &Emulator) {
+        let sprite = self.size.bytes() as i32;
+        match key {
+            Keycode::LEFT => self.scrub(emu, -1),
+            Keycode::RIGHT => self.scrub(emu, 1),
+            Keycode::UP => self.scrub(emu, -sprite),
+            Keycode::DOWN => self.scrub(emu, sprite),
+            Keycode::PAGEUP => self.scrub(emu, -sprite * PAGE_SPRITES),
+            Keycode::PAGEDOWN => self.scrub(emu, sprite * PAGE_SPRITES),
+            Keycode::HOME => {
+                self.follow_i = false;
+                self.address = 0;
+            }
+            Keycode::I => self.follow_i = true,
+            Keycode::S => {
+                self.size = match self.size {
+                    SpriteSize::Narrow(_) => SpriteSize::Wide,
+                    SpriteSize::Wide => SpriteSize::Narrow(FONT_HEIGHT as u8),
+                }
+            }
+            Keycode::EQUALS | Keycode::PLUS | Keycode::KP_PLUS => self.change_height(1),
+            Keycode::MINUS | Keycode::KP_MINUS => self.change_height(-1),
+            _ => {}
+        }
+    }
+
+    /// Scroll the address by `rows` sprites, for the mouse wheel
+    pub fn scroll(&mut self, emu: &Emulator, rows: i32) {
+        self.scrub(emu, rows * self.size.bytes() as i32);
+    }
+
+    /// Stop following I and move `offset` bytes from the address currently shown
+    fn scrub(&mut self, emu: &Emulator, offset: i32) {
+        let address = self.current_address(emu) as i32 + offset;
+        self.address = address.rem_euclid(RAM_SIZE as i32) as u16;
+        self.follow_i = false;
+    }
+
+    fn change_height(&mut self, change: i8) {
+        if let SpriteSize::Narrow(height) = self.size {
+            self.size = SpriteSize::Narrow(height.saturating_add_signed(change).clamp(1, 15));
+        }
+    }
+
+    fn current_address(&self, emu: &Emulator) -> u16 {
+        if self.follow_i {
+            emu.i()
+        } else {
+            self.address
+        }
+    }
+
+    /// Redraw the window with the current state of `emu`, if it's open
+    pub fn draw(&mut self, emu: &Emulator) -> Result<(), String> {
+        let address = self.current_address(emu);
+        let size = self.size;
+        let mode = if self.follow_i {
+            "FOLLOWING I, ARROWS SCRUB"
+        } else {
+            "SCRUBBING, I FOLLOWS I"
+        };
+        let Some(canvas) = self.window.canvas_to_draw() else {
+            return Ok(());
+        };
+
+        canvas.set_draw_color(HIGHLIGHT_COLOR);
+        let header = format!("{} SPRITE AT {:03X}", size.label(), address);
+        draw_text(canvas, &header, MARGIN, MARGIN, TEXT_SCALE)?;
+        canvas.set_draw_color(TEXT_COLOR);
+        draw_text(canvas, mode, MARGIN, MARGIN + LINE_HEIGHT, TEXT_SCALE)?;
+        draw_text(
+            canvas,
+            "S 8XN/16X16, +/- CHANGE N",
+            MARGIN,
+            MARGIN + LINE_HEIGHT * 2,
+            TEXT_SCALE,
+        )?;
+
+        draw_sprite(
+            canvas,
+            emu.ram(),
+            address,
+            size,
+            MARGIN,
+            PREVIEW_TOP,
+            PREVIEW_PIXEL,
+        )?;
+
+        canvas.set_draw_color(TEXT_COLOR);
+        draw_text(canvas, "NEXT", MARGIN, NEXT_TOP, TEXT_SCALE)?;
+        for index in 0..NEXT_ROWS * COLUMNS {
+            let sprite_address = address.wrapping_add((index as u16 + 1) * size.bytes());
+            let sprite_address = sprite_address % RAM_SIZE as u16;
+            let (x, y) = cell_position(index, NEXT_TOP + LINE_HEIGHT);
+            canvas.set_draw_color(TEXT_COLOR);
+            draw_text(canvas, &format!("{:03X}", sprite_address), x, y, TEXT_SCALE)?;
+            draw_sprite(
+                canvas,
+                emu.ram(),
+                sprite_address,
+                size,
+                x,
+                y + LINE_HEIGHT,
+                THUMBNAIL_PIXEL,
+            )?;
+        }
+
+        // the glyphs as they are in memory, with the one I points at highlighted,
+        // to check FX29 picks the right digit
+        canvas.set_draw_color(TEXT_COLOR);
+        draw_text(canvas, "FONT", MARGIN, FONT_TOP, TEXT_SCALE)?;
+        let font = SpriteSize::Narrow(FONT_HEIGHT as u8);
+        for digit in 0..FONT_SET_SIZE / FONT_HEIGHT {
+            let glyph_address = (digit * FONT_HEIGHT) as u16;
+            let (x, y) = cell_position(digit, FONT_TOP + LINE_HEIGHT);
+            canvas.set_draw_color(if emu.i() == glyph_address {
+                HIGHLIGHT_COLOR
+            } else {
+                TEXT_COLOR
+            });
+            draw_text(canvas, &format!("{:X}", digit), x, y, TEXT_SCALE)?;
+            draw_sprite(
+                canvas,
+                emu.ram(),
+                glyph_address,
+                font,
+                x,
+                y + LINE_HEIGHT,
+                THUMBNAIL_PIXEL,
+            )?;
+        }
+
+        canvas.present();
+        Ok(())
+    }
+}
+
+/// Top left corner of the `index`th cell of a grid starting at `top`
+fn cell_position(index: usize, top: i32) -> (i32, i32) {
+    let x = MARGIN + (index % COLUMNS) as i32 * CELL_WIDTH;
+    let y = top + (index / COLUMNS) as i32 * CELL_HEIGHT;
+    (x, y)
+}
+
+/// Draw the sprite at `address` with its top left corner at `x`, `y`, every sprite
+/// pixel becoming a `pixel` by `pixel` square. Sprites wrap around the end of memory
+fn draw_sprite(
+    canvas: &mut Canvas<Window>,
+    ram: &[u8],
+    address: u16,
+    size: SpriteSize,
+    x: i32,
+    y: i32,
+    pixel: u32,
+) -> Result<(), String> {
+    canvas.set_draw_color(OFF_COLOR);
+    canvas.fill_rect(Rect::new(x, y, size.width() * pixel, size.height() * pixel))?;
+
+    let bytes_per_row = size.width() / 8;
+    let mut pixels: Vec<Rect> = Vec::new();
+    for row in 0..size.height() {
+        for column in 0..size.width() {
+            let offset = (row * bytes_per_row + column / 8) as usize;
+            let byte = ram[(address as usize + offset) % ram.len()];
+            if (byte >> (7 - column % 8)) & 1 == 1 {
+                pixels.push(Rect::new(
+                    x + (column * pixel) as i32,
+                    y + (row * pixel) as i32,
+                    pixel,
+                    pixel,
+                ));
+            }
+        }
+    }
+    canvas.set_draw_color(TEXT_COLOR);
+    canvas.fill_rects(&pixels)
+}
@@ -0,0 +1,107 @@
+/// Labels for addresses, from a symbol file written by an assembler, to show code by name in
+/// backtraces and the inspector. Each line is a label and its address in hex, separated by
+/// spaces, `=` or `:`, e.g. `draw_score = 0x2A0`, and lines starting with `#` are comments
+use std::collections::BTreeMap;
+use std::fs;
+
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    labels: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+        Self::parse(&text).map_err(|e| format!("{} {}", file, e))
+    }
+
+    /// Labels from the contents of a symbol file
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut labels = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line
+                .split(|c: char| c.is_whitespace() || c == '=' || c == ':')
+                .filter(|part| !part.is_empty());
+            let parsed = match (parts.next(), parts.next(), parts.next()) {
+                (Some(label), Some(address), None) => {
+                    let address = address.trim_start_matches("0x").trim_start_matches("0X");
+                    u16::from_str_radix(address, 16)
+                        .ok()
+                        .map(|address| (address, label.to_string()))
+                }
+                _ => None,
+            };
+            let (address, label) = parsed.ok_or_else(|| {
+                format!(
+                    "line {} is not a label and an address: {}",
+                    number + 1,
+                    line
+                )
+            })?;
+            labels.insert(address, label);
+        }
+        Ok(Self { labels })
+    }
+
+    /// `address` along with the nearest label at or before it, e.g. `2A4 draw_score+4`
+    pub fn describe(&self, address: u16) -> String {
+        match self.labels.range(..=address).next_back() {
+            Some((start, label)) if *start == address => format!("{:03X} {}", address, label),
+            Some((start, label)) => {
+                format!("{:03X} {}+{:X}", address, label, address - start)
+            }
+            None => format!("{:03X}", address),
+        }
+    }
+
+    /// `pc` and the calls that led to it, the most recent first
+    pub fn backtrace(&self, pc: u16, stack: &[u16]) -> Vec<String> {
+        let mut backtrace = vec![self.describe(pc)];
+        // the stack holds return addresses, the calls were the instructions before them
+        backtrace.extend(
+            stack
+                .iter()
+                .rev()
+                .map(|address| self.describe(address.wrapping_sub(2))),
+        );
+        backtrace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators_and_comments() {
+        let symbols =
+            Symbols::parse("# labels\nmain = 0x200\n\ndraw_score: 2a0\n  loop 0X2B0  \n").unwrap();
+        assert_eq!(symbols.describe(0x200), "200 main");
+        assert_eq!(symbols.describe(0x21E), "21E main+1E");
+        assert_eq!(symbols.describe(0x2A4), "2A4 draw_score+4");
+        assert_eq!(symbols.describe(0x2B0), "2B0 loop");
+        assert_eq!(symbols.describe(0x1FE), "1FE");
+        assert_eq!(
+            symbols.backtrace(0x2A4, &[0x206, 0x220]),
+            vec!["2A4 draw_score+4", "21E main+1E", "204 main+4"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for text in [
+            "main\n",
+            "main = 0x200 0x300\n",
+            "main = zzz\n",
+            "main = 0x10000\n",
+        ] {
+            let error = Symbols::parse(&format!("start = 0x200\n{}", text)).unwrap_err();
+            assert!(error.starts_with("line 2 "), "{}", error);
+        }
+    }
+}
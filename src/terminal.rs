@@ -0,0 +1,335 @@
+/// Frontend drawing the screen in a terminal with half block characters, two CHIP-8 rows
+/// to a line of text, and reading keys from stdin in raw mode, so the emulator can run over
+/// SSH without SDL. Raw mode is set and restored with `stty`
+use std::cell::Cell;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::keypad::HostKey;
+use crate::palette::Palette;
+use crate::sound::{AudioBackend, Tone};
+
+// Terminals only send key presses, with repeats while a key is held, so a key counts as
+// released once it hasn't been repeated for this long. Repeats only start after a delay of
+// up to about 660ms, so until the first one a key is held for longer
+const KEY_HOLD: Duration = Duration::from_millis(200);
+const KEY_REPEAT_DELAY: Duration = Duration::from_millis(750);
+// How long to wait for the rest of an escape sequence before taking ESC as the Escape key
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalEvent {
+    KeyDown(HostKey),
+    KeyUp(HostKey),
+    Quit, // Ctrl-C or Escape
+}
+
+pub struct Terminal {
+    saved_mode: String, // stty settings from before raw mode, restored on drop
+    held: Vec<HeldKey>,
+    pending: Vec<u8>, // the start of an escape sequence, waiting for the rest of it
+    pending_since: Instant,
+    message: Option<(String, Instant)>,
+}
+
+/// A key that was pressed, and when it was last repeated
+struct HeldKey {
+    key: HostKey,
+    last_seen: Instant,
+    repeated: bool,
+}
+
+impl Terminal {
+    /// Put the terminal in raw mode and switch to the alternate screen
+    pub fn open() -> Result<Self, String> {
+        let saved = Command::new("stty")
+            .arg("-g")
+            .stdin(Stdio::inherit())
+            .output()
+            .map_err(|e| format!("Could not run stty: {}", e))?;
+        if !saved.status.success() {
+            return Err("stdin is not a terminal".to_string());
+        }
+        stty(&["raw", "-echo", "min", "0", "time", "0"])?;
+        let terminal = Self {
+            saved_mode: String::from_utf8_lossy(&saved.stdout).trim().to_string(),
+            held: Vec::new(),
+            pending: Vec::new(),
+            pending_since: Instant::now(),
+            message: None,
+        };
+        // alternate screen, hide the cursor, clear
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        Ok(terminal)
+    }
+
+    /// Show `message` on the status line for a couple of seconds
+    pub fn show_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    /// The message being shown, if it hasn't expired
+    pub fn message(&mut self) -> Option<&str> {
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= MESSAGE_DURATION)
+        {
+            self.message = None;
+        }
+        self.message.as_ref().map(|(message, _)| message.as_str())
+    }
+
+    /// Keys pressed since the last poll, and releases of keys that stopped repeating
+    pub fn poll_events(&mut self) -> Vec<TerminalEvent> {
+        let mut input = std::mem::take(&mut self.pending);
+        let mut buffer = [0; 64];
+        while let Ok(read @ 1..) = io::stdin().read(&mut buffer) {
+            input.extend_from_slice(&buffer[..read]);
+        }
+
+        let (mut keys, parsed) = parse_keys(&input);
+        if parsed < input.len() {
+            if self.pending_since.elapsed() < ESCAPE_TIMEOUT {
+                self.pending = input.split_off(parsed);
+            } else if input[parsed..] == [0x1B] {
+                keys.push(None); // nothing followed it, so Escape was pressed
+            }
+        }
+        if self.pending.is_empty() {
+            self.pending_since = Instant::now();
+        }
+
+        let mut events = Vec::new();
+        for key in keys {
+            let Some(key) = key else {
+                events.push(TerminalEvent::Quit);
+                continue;
+            };
+            match self.held.iter_mut().find(|held| held.key == key) {
+                Some(held) => {
+                    held.last_seen = Instant::now();
+                    held.repeated = true;
+                }
+                None => {
+                    self.held.push(HeldKey {
+                        key,
+                        last_seen: Instant::now(),
+                        repeated: false,
+                    });
+                    events.push(TerminalEvent::KeyDown(key));
+                }
+            }
+        }
+        self.held.retain(|held| {
+            let hold = if held.repeated {
+                KEY_HOLD
+            } else {
+                KEY_REPEAT_DELAY
+            };
+            let still_held = held.last_seen.elapsed() < hold;
+            if !still_held {
+                events.push(TerminalEvent::KeyUp(held.key));
+            }
+            still_held
+        });
+        events
+    }
+
+    /// Draw the screen in `palette`'s colours with `status` on the line below it
+    pub fn draw(
+        &self,
+        screen: &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
+        palette: &Palette,
+        status: &str,
+    ) -> Result<(), String> {
+        let [fr, fg, fb] = palette.foreground;
+        let [br, bg, bb] = palette.background;
+        let mut frame = format!(
+            "\x1b[H\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+            fr, fg, fb, br, bg, bb
+        );
+        for rows in screen.chunks(2) {
+            for x in 0..SCREEN_WIDTH {
+                frame.push(match (rows[0][x], rows.get(1).is_some_and(|row| row[x])) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            frame.push_str("\r\n");
+        }
+        // clear the rest of the status line in case the last one was longer
+        frame.push_str(&format!("\x1b[0m{}\x1b[K", status));
+
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(frame.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        io::stdout().flush().ok();
+        stty(&[&self.saved_mode]).ok();
+    }
+}
+
+fn stty(settings: &[&str]) -> Result<(), String> {
+    let status = Command::new("stty")
+        .args(settings)
+        .stdin(Stdio::inherit())
+        .status()
+        .map_err(|e| format!("Could not run stty: {}", e))?;
+    if !status.success() {
+        return Err("Could not set the terminal mode with stty".to_string());
+    }
+    Ok(())
+}
+
+/// Keys in raw terminal input, None for Ctrl-C or Escape, and how many bytes of it were
+/// parsed. An escape sequence cut off at the end of the input is left to be parsed along
+/// with the rest of it. Letters are lowercased, since Shift can't be told apart from Caps
+/// Lock, and Alt with a key and unknown escape sequences are skipped
+fn parse_keys(input: &[u8]) -> (Vec<Option<HostKey>>, usize) {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < input.len() {
+        let key_start = i;
+        let byte = input[i];
+        i += 1;
+        let key = match (byte, input.get(i)) {
+            (0x03, _) => None,
+            (0x1B, None) => return (keys, key_start),
+            (0x1B, Some(0x1B)) => None,
+            (0x1B, Some(b'[' | b'O')) => {
+                // CSI or SS3 sequence, parameters up to a final byte from @ to ~
+                let start = i + 1;
+                let Some(end) = input[start..]
+                    .iter()
+                    .position(|b| (0x40..=0x7E).contains(b))
+                    .map(|end| start + end)
+                else {
+                    return (keys, key_start);
+                };
+                i = end + 1;
+                let Some(key) = escape_key(&input[start..end], input[end]) else {
+                    continue;
+                };
+                Some(key)
+            }
+            (0x1B, Some(_)) => {
+                i += 1; // Alt and a key
+                continue;
+            }
+            (b'\t', _) => Some(HostKey::Tab),
+            (b' ', _) => Some(HostKey::Space),
+            _ if byte.is_ascii_alphanumeric() => {
+                Some(HostKey::Char(byte.to_ascii_lowercase() as char))
+            }
+            _ => continue,
+        };
+        keys.push(key);
+    }
+    (keys, i)
+}
+
+/// The key sent as an escape sequence with `parameters` and the final byte `last`
+fn escape_key(parameters: &[u8], last: u8) -> Option<HostKey> {
+    match (parameters, last) {
+        ([], b'A') => Some(HostKey::Up),
+        ([], b'B') => Some(HostKey::Down),
+        ([], b'C') => Some(HostKey::Right),
+        ([], b'D') => Some(HostKey::Left),
+        ([], b'P'..=b'S') => Some(HostKey::Function(last - b'P' + 1)),
+        (_, b'~') => {
+            let number: u8 = std::str::from_utf8(parameters).ok()?.parse().ok()?;
+            match number {
+                11..=15 => Some(HostKey::Function(number - 10)),
+                17..=21 => Some(HostKey::Function(number - 11)),
+                23 | 24 => Some(HostKey::Function(number - 12)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Beeps with the terminal bell when the sound timer starts, since a terminal can't hold
+/// a tone
+pub struct TerminalBell {
+    beeping: Cell<bool>,
+}
+
+impl TerminalBell {
+    pub fn new() -> Self {
+        Self {
+            beeping: Cell::new(false),
+        }
+    }
+}
+
+impl AudioBackend for TerminalBell {
+    fn handle_sound_timer(&self, timer: &u8) {
+        let beeping = *timer > 0;
+        if beeping && !self.beeping.get() {
+            print!("\x07");
+            io::stdout().flush().ok();
+        }
+        self.beeping.set(beeping);
+    }
+
+    fn set_tone(&mut self, _tone: Tone) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_and_escape_sequences() {
+        let input = b"aB5 \t\x1b[A\x1bOP\x1b[15~\x1b[200~\x03";
+        let (keys, parsed) = parse_keys(input);
+        assert_eq!(
+            keys,
+            vec![
+                Some(HostKey::Char('a')),
+                Some(HostKey::Char('b')),
+                Some(HostKey::Char('5')),
+                Some(HostKey::Space),
+                Some(HostKey::Tab),
+                Some(HostKey::Up),
+                Some(HostKey::Function(1)),
+                Some(HostKey::Function(5)),
+                None,
+            ]
+        );
+        assert_eq!(parsed, input.len());
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(parse_keys(b"\x1b\x1b"), (vec![None], 1));
+        // Alt and a key isn't Escape
+        assert_eq!(parse_keys(b"\x1bq"), (vec![], 2));
+    }
+
+    #[test]
+    fn escape_sequences_split_across_reads_are_left_for_later() {
+        assert_eq!(parse_keys(b"w\x1b"), (vec![Some(HostKey::Char('w'))], 1));
+        assert_eq!(parse_keys(b"w\x1b["), (vec![Some(HostKey::Char('w'))], 1));
+        assert_eq!(parse_keys(b"w\x1b[1"), (vec![Some(HostKey::Char('w'))], 1));
+        assert_eq!(
+            parse_keys(b"\x1b[15~"),
+            (vec![Some(HostKey::Function(5))], 5)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+/// Writes a line for every instruction executed, in a fixed format that's easy to grep and
+/// to diff against traces from other emulators:
+///
+/// `        42 208 7001   ADD V0, 0x01       V0=05 I=2A0`
+///
+/// that's the cycle, PC, opcode, the instruction in assembly, the registers it changed and I
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+
+use crate::emulator::{Emulator, NUM_VARIABLE_REGISTERS};
+use crate::opcode::Opcode;
+
+/// Which instructions to trace, by the address they're at and the opcode class, the
+/// first hex digit of the opcode. Empty lists trace everything
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    pub ranges: Vec<RangeInclusive<u16>>,
+    pub classes: Vec<u8>,
+}
+
+impl TraceFilter {
+    fn matches(&self, pc: u16, opcode: u16) -> bool {
+        let class = (opcode >> 12) as u8;
+        (self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&pc)))
+            && (self.classes.is_empty() || self.classes.contains(&class))
+    }
+}
+
+/// State before an instruction, to compare with after it
+pub struct TraceEntry {
+    pc: u16,
+    opcode: u16,
+    registers: [u8; NUM_VARIABLE_REGISTERS],
+}
+
+pub struct Tracer {
+    out: BufWriter<File>,
+    filter: TraceFilter,
+}
+
+impl Tracer {
+    pub fn create(file: &str, filter: TraceFilter) -> Result<Self, String> {
+        let out = File::create(file).map_err(|e| format!("Could not create {}: {}", file, e))?;
+        Ok(Self {
+            out: BufWriter::new(out),
+            filter,
+        })
+    }
+
+    /// Note the state before the next instruction, None if it's filtered out
+    pub fn before(&self, emu: &Emulator) -> Option<TraceEntry> {
+        let pc = emu.pc();
+        let opcode = emu.instruction_at(pc);
+        self.filter.matches(pc, opcode).then(|| TraceEntry {
+            pc,
+            opcode,
+            registers: *emu.registers(),
+        })
+    }
+
+    /// Write the line for the instruction `entry` was taken before
+    pub fn after(&mut self, entry: TraceEntry, emu: &Emulator) -> io::Result<()> {
+        let changed: Vec<String> = emu
+            .registers()
+            .iter()
+            .zip(entry.registers.iter())
+            .enumerate()
+            .filter(|(_, (after, before))| after != before)
+            .map(|(reg, (after, _))| format!("V{:X}={:02X}", reg, after))
+            .collect();
+        let mut changed = changed.join(" ");
+        if !changed.is_empty() {
+            changed.push(' ');
+        }
+        writeln!(
+            self.out,
+            "{:>10} {:03X} {:04X}   {:<18} {}I={:03X}",
+            emu.cycles(),
+            entry.pc,
+            entry.opcode,
+            Opcode::decode(entry.opcode).to_string(),
+            changed,
+            emu.i()
+        )
+    }
+}
+
+/// Parse an address range for the trace filter, e.g. `200-2FF`, or a single address
+pub fn parse_range(range: &str) -> Result<RangeInclusive<u16>, String> {
+    let parse = |address: &str| {
+        u16::from_str_radix(address.trim().trim_start_matches("0x"), 16)
+            .map_err(|_| format!("{} is not an address range like 200-2FF", range))
+    };
+    match range.split_once('-') {
+        Some((start, end)) => Ok(parse(start)?..=parse(end)?),
+        None => {
+            let address = parse(range)?;
+            Ok(address..=address)
+        }
+    }
+}
+
+/// Parse an opcode class for the trace filter, the first hex digit of the opcode, e.g. `D`
+pub fn parse_class(class: &str) -> Result<u8, String> {
+    match class.chars().next().and_then(|c| c.to_digit(16)) {
+        Some(digit) if class.len() == 1 => Ok(digit as u8),
+        _ => Err(format!("{} is not an opcode class from 0 to F", class)),
+    }
+}
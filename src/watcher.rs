@@ -0,0 +1,42 @@
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a file for changes by polling its modification time
+pub struct FileWatcher {
+    file: String,
+    last_modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            last_modified: modified_time(file),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Tells if the file has been modified since the last time this returned true.
+    /// Only checks the file system a couple of times per second
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = modified_time(&self.file);
+        // a missing file is usually an editor part way through saving it
+        if modified.is_some() && modified != self.last_modified {
+            self.last_modified = modified;
+            return true;
+        }
+        false
+    }
+}
+
+fn modified_time(file: &str) -> Option<SystemTime> {
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
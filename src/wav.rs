@@ -0,0 +1,108 @@
+/// Renders the beeper to a WAV file in emulated time, so sound can be checked without
+/// an audio device. Format reference: http://soundfile.sapp.org/doc/WaveFormat/
+use std::fs;
+
+use crate::sound::{SquareWave, Tone, SAMPLE_RATE};
+
+const FRAMES_PER_SECOND: i32 = 60;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FRAMES_PER_SECOND) as usize;
+const BITS_PER_SAMPLE: u16 = 16;
+const NUM_CHANNELS: u16 = 1; // mono, same as the SDL playback device
+
+pub struct WavRecorder {
+    wave: SquareWave,
+    samples: Vec<i16>,
+}
+
+impl WavRecorder {
+    pub fn new(tone: Tone) -> Self {
+        Self {
+            wave: SquareWave::new(SAMPLE_RATE, tone),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Generate one 60Hz frame worth of samples, the tone if the sound timer is
+    /// greater than 0, silence otherwise
+    pub fn record_frame(&mut self, timer: &u8) {
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = if *timer > 0 {
+                (self.wave.next_sample() * i16::MAX as f32) as i16
+            } else {
+                0
+            };
+            self.samples.push(sample);
+        }
+    }
+
+    /// Write all the recorded samples to `file` as a 16-bit PCM WAV
+    pub fn save(&self, file: &str) -> Result<(), String> {
+        fs::write(file, self.encode()).map_err(|e| e.to_string())
+    }
+
+    /// The recorded samples as the contents of a 16-bit PCM WAV file
+    fn encode(&self) -> Vec<u8> {
+        let block_align = NUM_CHANNELS * BITS_PER_SAMPLE / 8;
+        let byte_rate = SAMPLE_RATE as u32 * block_align as u32;
+        let data_size = (self.samples.len() * block_align as usize) as u32;
+
+        let mut bytes: Vec<u8> = Vec::with_capacity(44 + data_size as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes()); // size of the rest of this chunk
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&NUM_CHANNELS.to_le_bytes());
+        bytes.extend_from_slice(&(SAMPLE_RATE as u32).to_le_bytes());
+        bytes.extend_from_slice(&byte_rate.to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let mut recorder = WavRecorder::new(Tone::default());
+        recorder.record_frame(&1);
+        recorder.record_frame(&0);
+        let bytes = recorder.encode();
+
+        let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let data_size = 2 * SAMPLES_PER_FRAME as u32 * 2;
+        assert_eq!(bytes.len(), 44 + data_size as usize);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + data_size);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(16), 16);
+        assert_eq!(u16_at(20), 1); // PCM
+        assert_eq!(u16_at(22), 1); // mono
+        assert_eq!(u32_at(24), 44100);
+        assert_eq!(u32_at(28), 44100 * 2); // byte rate
+        assert_eq!(u16_at(32), 2); // block align
+        assert_eq!(u16_at(34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(40), data_size);
+
+        // the tone, then silence
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect();
+        assert!(samples[..SAMPLES_PER_FRAME].iter().any(|&s| s != 0));
+        assert!(samples[SAMPLES_PER_FRAME..].iter().all(|&s| s == 0));
+    }
+}